@@ -3,6 +3,7 @@ import { getUser, logoutUser } from "./apis/api";
 import { User } from "./bindings/User";
 import { Link } from "react-router-dom";
 import GiftSettingsPanel from "./components/GiftSettingsPanel";
+import GuardScSettingsPanel from "./components/GuardScSettingsPanel";
 
 const queryUser = async (): Promise<User | null> => {
 	const res = await getUser();
@@ -44,6 +45,7 @@ const Config = () => {
 						</button>
 					</div>
 					<GiftSettingsPanel />
+					<GuardScSettingsPanel />
 				</div>
 			) : (
 				<div>
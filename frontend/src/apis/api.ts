@@ -2,9 +2,17 @@
 /// A direct correspondence with backend's interface
 
 import { DanmujiApiResponse } from "../bindings/DanmujiApiResponse";
+import { DedupStats } from "../bindings/DedupStats";
 import { GiftThankConfig } from "../bindings/GiftThankConfig";
+import { PkSession } from "../bindings/PkSession";
+import { PluginInfo } from "../bindings/PluginInfo";
 import { QrCode } from "../bindings/QrCode";
 import { Room } from "../bindings/room";
+import { RoomLatency } from "../bindings/RoomLatency";
+import { TemplatePreview } from "../bindings/TemplatePreview";
+import { TemplateRequest } from "../bindings/TemplateRequest";
+import { TemplateValidation } from "../bindings/TemplateValidation";
+import { UnhandledCommands } from "../bindings/UnhandledCommands";
 import { User } from "../bindings/user";
 
 const baseUrl = "/api";
@@ -70,6 +78,28 @@ const getRoomStatus = async (): Promise<DanmujiApiResponse<Room>> => {
 	return await danmujiFetch<Room>(`${baseUrl}/roomStatus`);
 };
 
+/// query heartbeat round-trip time of the connected room
+const getRoomLatency = async (): Promise<DanmujiApiResponse<RoomLatency>> => {
+	return await danmujiFetch<RoomLatency>(`${baseUrl}/roomLatency`);
+};
+
+/// query notification commands of the connected room without a typed message
+const getUnhandledCommands = async (): Promise<
+	DanmujiApiResponse<UnhandledCommands>
+> => {
+	return await danmujiFetch<UnhandledCommands>(`${baseUrl}/unhandledCommands`);
+};
+
+/// query the latest PK battle of the connected room
+const getRoomPk = async (): Promise<DanmujiApiResponse<PkSession>> => {
+	return await danmujiFetch<PkSession>(`${baseUrl}/roomPk`);
+};
+
+/// query how many duplicate messages of the connected room have been dropped
+const getDedupStats = async (): Promise<DanmujiApiResponse<DedupStats>> => {
+	return await danmujiFetch<DedupStats>(`${baseUrl}/dedupStats`);
+};
+
 const disconnect = async (): Promise<DanmujiApiResponse<void>> => {
 	return await danmujiFetch(`${baseUrl}/disconnect`, "POST");
 };
@@ -90,6 +120,53 @@ const setGiftConfig = async (
 	);
 };
 
+const listPlugins = async (): Promise<DanmujiApiResponse<PluginInfo[]>> => {
+	return await danmujiFetch<PluginInfo[]>(`${baseUrl}/plugins`);
+};
+
+const enablePlugin = async (
+	name: string
+): Promise<DanmujiApiResponse<void>> => {
+	return await danmujiFetch<void>(`${baseUrl}/plugins/${name}/enable`, "POST");
+};
+
+const disablePlugin = async (
+	name: string
+): Promise<DanmujiApiResponse<void>> => {
+	return await danmujiFetch<void>(`${baseUrl}/plugins/${name}/disable`, "POST");
+};
+
+const configurePlugin = async (
+	name: string,
+	config: any
+): Promise<DanmujiApiResponse<void>> => {
+	return await danmujiFetch<void>(
+		`${baseUrl}/plugins/${name}/config`,
+		"POST",
+		JSON.stringify(config)
+	);
+};
+
+const validateTemplate = async (
+	request: TemplateRequest
+): Promise<DanmujiApiResponse<TemplateValidation>> => {
+	return await danmujiFetch<TemplateValidation>(
+		`${baseUrl}/templates/validate`,
+		"POST",
+		JSON.stringify(request)
+	);
+};
+
+const previewTemplate = async (
+	request: TemplateRequest
+): Promise<DanmujiApiResponse<TemplatePreview>> => {
+	return await danmujiFetch<TemplatePreview>(
+		`${baseUrl}/templates/preview`,
+		"POST",
+		JSON.stringify(request)
+	);
+};
+
 export {
 	getUser,
 	qrcode,
@@ -97,7 +174,17 @@ export {
 	logoutUser,
 	roomInit,
 	getRoomStatus,
+	getRoomLatency,
+	getUnhandledCommands,
+	getRoomPk,
+	getDedupStats,
 	disconnect,
 	getGiftConfig,
 	setGiftConfig,
+	listPlugins,
+	enablePlugin,
+	disablePlugin,
+	configurePlugin,
+	validateTemplate,
+	previewTemplate,
 };
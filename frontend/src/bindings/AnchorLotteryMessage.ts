@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface AnchorLotteryMessage { id: number, award_name: string, award_num: number, danmu: string, require_text: string, gift_name: string | null, gift_num: number, max_time: number, }
\ No newline at end of file
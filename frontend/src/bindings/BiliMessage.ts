@@ -1,5 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AnchorLotteryMessage } from "./AnchorLotteryMessage";
 import type { DanmuMessage } from "./DanmuMessage";
 import type { GiftMessage } from "./GiftMessage";
+import type { GuardEntryMessage } from "./GuardEntryMessage";
+import type { GuardPurchaseMessage } from "./GuardPurchaseMessage";
+import type { InteractionMessage } from "./InteractionMessage";
+import type { LotteryWinner } from "./LotteryWinner";
+import type { OnlineRankUser } from "./OnlineRankUser";
+import type { PkEvent } from "./PkEvent";
+import type { RedPocketMessage } from "./RedPocketMessage";
+import type { RoomEvent } from "./RoomEvent";
+import type { SuperChatMessage } from "./SuperChatMessage";
 
-export type BiliMessage = { type: "Danmu", body: DanmuMessage } | { type: "Gift", body: GiftMessage } | { type: "RoomPopularity", body: number };
\ No newline at end of file
+export type BiliMessage = { "type": "Danmu", "body": DanmuMessage } | { "type": "Gift", "body": GiftMessage } | { "type": "SuperChat", "body": SuperChatMessage } | { "type": "SuperChatDelete", "body": { ids: Array<number>, } } | { "type": "Interaction", "body": InteractionMessage } | { "type": "GuardEntry", "body": GuardEntryMessage } | { "type": "GuardPurchase", "body": GuardPurchaseMessage } | { "type": "RedPocketStart", "body": RedPocketMessage } | { "type": "RedPocketWinners", "body": { lot_id: number, winners: Array<LotteryWinner>, } } | { "type": "AnchorLotteryStart", "body": AnchorLotteryMessage } | { "type": "AnchorLotteryEnd", "body": { id: number, } } | { "type": "AnchorLotteryAward", "body": { id: number, award_name: string, winners: Array<LotteryWinner>, } } | { "type": "Pk", "body": PkEvent } | { "type": "RoomEvent", "body": RoomEvent } | { "type": "RoomPopularity", "body": number } | { "type": "OnlineRankCount", "body": { count: number, } } | { "type": "OnlineRankTop", "body": Array<OnlineRankUser> } | { "type": "WatchedChange", "body": { num: number, text: string, } } | { "type": "LikeCount", "body": { count: number, } } | { "type": "Raw", "body": { cmd: string, body: any, } } | { "type": "Connecting", "body": { room_id: number, attempt: number, } } | { "type": "Connected", "body": { room_id: number, url: string, } } | { "type": "EntryAcknowledged", "body": { room_id: number, } } | { "type": "Disconnected", "body": { room_id: number, reason: string, } } | { "type": "GaveUp", "body": { room_id: number, attempts: number, } };
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConfigFieldKind } from "./ConfigFieldKind";
+
+export interface ConfigField { name: string, kind: ConfigFieldKind, description: string, }
\ No newline at end of file
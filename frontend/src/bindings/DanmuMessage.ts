@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Emoticon } from "./Emoticon";
 import type { GuardType } from "./GuardType";
 import type { Medal } from "./Medal";
 
-export interface DanmuMessage { uid: bigint, uname: string, content: string, is_gift_auto: boolean, sent_time: bigint, is_manager: boolean, is_vip: boolean, is_svip: boolean, is_full_member: boolean, medal: Medal | null, ul: bigint, ul_rank: string, guard: GuardType, }
\ No newline at end of file
+export interface DanmuMessage { uid: bigint, uname: string, content: string, is_gift_auto: boolean, sent_time: bigint, is_manager: boolean, is_vip: boolean, is_svip: boolean, is_full_member: boolean, medal: Medal | null, ul: bigint, ul_rank: string, guard: GuardType, mode: number, color: string, id_str: string | null, emoticon: Emoticon | null, reply_uname: string | null, }
\ No newline at end of file
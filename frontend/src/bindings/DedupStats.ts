@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface DedupStats { checked: number, danmu: number, gift: number, super_chat: number, }
\ No newline at end of file
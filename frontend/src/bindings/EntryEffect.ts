@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface EntryEffect { id: number, priority: number, copy_color: string, highlight_color: string, basemap_url: string, effective_time: number, }
\ No newline at end of file
@@ -1,4 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BlindGift } from "./BlindGift";
+import type { CoinType } from "./CoinType";
+import type { GiftReceiver } from "./GiftReceiver";
 import type { GuardType } from "./GuardType";
+import type { Medal } from "./Medal";
 
-export interface GiftMessage { uid: bigint, uname: string, guard: GuardType, gift_id: bigint, gift_name: string, gift_num: number, }
\ No newline at end of file
+export interface GiftMessage { uid: bigint, uname: string, guard: GuardType, gift_id: bigint, gift_name: string, gift_num: number, price: number, coin_type: CoinType, total_coin: number, tid: string | null, batch_combo_id: string | null, blind_gift: BlindGift | null, receiver: GiftReceiver | null, medal: Medal | null, }
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ThankRule } from "./ThankRule";
 
-export interface GiftThankConfig { template: string, open: boolean, }
\ No newline at end of file
+export interface GiftThankConfig { template: string, open: boolean, aggregate: boolean, aggregate_window_secs: number, max_thanks_per_window: number, summary_template: string, rules: Array<ThankRule>, }
\ No newline at end of file
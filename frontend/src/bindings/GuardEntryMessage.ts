@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EntryEffect } from "./EntryEffect";
+import type { GuardType } from "./GuardType";
+
+export interface GuardEntryMessage { uid: number, uname: string, guard: GuardType, copy_writing: string, effect: EntryEffect | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GuardType } from "./GuardType";
+
+export interface GuardPurchaseMessage { uid: number, uname: string, guard: GuardType, months: number, price: number, is_renewal: boolean, }
\ No newline at end of file
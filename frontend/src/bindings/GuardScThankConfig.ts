@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface GuardScThankConfig { guard_open: boolean, guard_template: string, super_chat_open: boolean, super_chat_template: string, excerpt_len: number, }
\ No newline at end of file
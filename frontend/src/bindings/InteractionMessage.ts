@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GuardType } from "./GuardType";
+import type { InteractionType } from "./InteractionType";
+import type { Medal } from "./Medal";
+
+export interface InteractionMessage { uid: number, uname: string, kind: InteractionType, medal: Medal | null, guard: GuardType, timestamp: number, }
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GuardType } from "./GuardType";
 
-export interface Medal { level: bigint, name: string, streamer_name: string, streamer_roomid: bigint, }
\ No newline at end of file
+export interface Medal { level: bigint, name: string, streamer_name: string, streamer_roomid: bigint, color: string, color_border: string, color_start: string, color_end: string, guard: GuardType, is_lighted: boolean, }
\ No newline at end of file
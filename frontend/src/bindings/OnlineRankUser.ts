@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GuardType } from "./GuardType";
+
+export interface OnlineRankUser { uid: number, uname: string, face: string, rank: number, score: number, guard: GuardType, }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PkOpponent } from "./PkOpponent";
+import type { PkScore } from "./PkScore";
+
+export type PkEvent = { "kind": "Pre", pk_id: number, opponent: PkOpponent, } | { "kind": "Start", pk_id: number, init_room_id: number, matched_room_id: number, start_time: number, end_time: number, } | { "kind": "Progress", pk_id: number, init: PkScore, matched: PkScore, } | { "kind": "End", pk_id: number, init: PkScore, matched: PkScore, winner_room_id: number | null, };
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PkResult } from "./PkResult";
+
+export interface PkSession { pk_id: number, opponent_room_id: number, opponent_uname: string, score: number, opponent_score: number, end_time: number, result: PkResult | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConfigField } from "./ConfigField";
+
+export interface PluginInfo { name: string, description: string, enabled: boolean, config: any, schema: Array<ConfigField>, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RedPocketAward } from "./RedPocketAward";
+
+export interface RedPocketMessage { lot_id: number, sender_uid: number, sender_name: string, danmu: string, start_time: number, end_time: number, awards: Array<RedPocketAward>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RoomEvent = { "kind": "Live", live_time: number | null, } | { "kind": "Preparing" } | { "kind": "RoomChange", title: string, area_name: string, parent_area_name: string, } | { "kind": "CutOff", msg: string, } | { "kind": "Warning", msg: string, };
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GuardType } from "./GuardType";
+
+export interface SuperChatMessage { id: number, uid: number, uname: string, guard: GuardType, price: number, message: string, message_trans: string | null, duration: number, start_time: number, background_color: string, background_bottom_color: string, background_price_color: string, message_font_color: string, }
\ No newline at end of file
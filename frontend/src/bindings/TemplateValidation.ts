@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TemplateError } from "./TemplateError";
+
+export interface TemplateValidation { error: TemplateError | null, variables: Array<string>, }
\ No newline at end of file
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CoinType } from "./CoinType";
+import type { GuardType } from "./GuardType";
+import type { ThankAction } from "./ThankAction";
+
+export interface ThankRule { gift_id: number | null, gift_name: string | null, coin_type: CoinType | null, min_num: number | null, min_value: number | null, min_guard: GuardType | null, has_room_medal: boolean | null, action: ThankAction, }
\ No newline at end of file
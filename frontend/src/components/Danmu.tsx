@@ -36,9 +36,19 @@ const Danmu = ({ danmu }: DanmuProp) => {
 			<span className="after:content-[':'] text-cyan-200 shadowed-text mx-px">
 				{danmu.uname}
 			</span>
-			<span className="text-white shadowed-text mx-px">
-				{danmu.content}
-			</span>
+			{danmu.emoticon ? (
+				<img
+					className="h-12 mx-px"
+					src={danmu.emoticon.url}
+					alt={danmu.content}
+					referrerPolicy="no-referrer"
+				/>
+			) : (
+				<span className="text-white shadowed-text mx-px">
+					{danmu.reply_uname ? `@${danmu.reply_uname} ` : null}
+					{danmu.content}
+				</span>
+			)}
 		</div>
 	);
 };
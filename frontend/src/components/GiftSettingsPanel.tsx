@@ -1,6 +1,8 @@
 import { useCallback, useEffect, useState } from "react";
-import { getGiftConfig, setGiftConfig } from "../apis/api";
+import { getGiftConfig, previewTemplate, setGiftConfig } from "../apis/api";
 import { GiftThankConfig } from "../bindings/GiftThankConfig";
+import { TemplateEvent } from "../bindings/TemplateEvent";
+import { ThankRule } from "../bindings/ThankRule";
 
 const placeholder_text = `示例模版：
 感谢{ uname }送来的{ gift_num }个{ gift_name }
@@ -10,6 +12,39 @@ const placeholder_text = `示例模版：
 {gift_name}: 礼物名称
 `;
 
+const summary_placeholder_text = `示例模版：
+感谢{ uname }投喂的{ gifts }
+目前可用的汇总macro:
+{uname}: 送礼用户名
+{gifts}: 汇总的礼物, 如"2个小花花、5个辣条"
+`;
+
+const rules_placeholder_text = `按顺序匹配的规则(JSON), 例如:
+[
+  { "coin_type": "Silver", "action": { "kind": "Silent" } },
+  { "min_value": 100, "action": { "kind": "Thank", "template": "谢谢{uname}老板!" } }
+]
+可用条件: gift_id, gift_name, coin_type, min_num, min_value(元), min_guard, has_room_medal(佩戴本直播间的勋章)
+`;
+
+// render the template in the textarea with a sample event
+const preview = async (textareaId: string, event: TemplateEvent) => {
+	const template = (document.getElementById(textareaId) as HTMLTextAreaElement)
+		.value;
+	if (!template) {
+		return;
+	}
+	const res = await previewTemplate({ template, event });
+	if (!res.success || res.payload === null) {
+		alert("预览失败");
+	} else if (res.payload.error !== null) {
+		const { msg, line, column } = res.payload.error;
+		alert(`模版错误 (第${line}行, 第${column}列): ${msg}`);
+	} else {
+		alert(`预览: ${res.payload.preview}`);
+	}
+};
+
 const GiftSettingsPanel = () => {
 	const [config, setConfig] = useState<GiftThankConfig>(null);
 
@@ -60,9 +95,48 @@ const GiftSettingsPanel = () => {
 						template = config.template;
 					}
 
+					// aggregated thanks
+					const form = e.target as HTMLFormElement;
+					const aggregate = (
+						form.querySelector("#aggregate") as HTMLInputElement
+					).checked;
+					const windowSecs = parseInt(
+						(form.querySelector("#window") as HTMLInputElement).value
+					);
+					const maxThanks = parseInt(
+						(form.querySelector("#max_thanks") as HTMLInputElement).value
+					);
+					let summary_template = (
+						form.querySelector("#summary_template") as HTMLTextAreaElement
+					).value;
+					if (!summary_template && config) {
+						summary_template = config.summary_template;
+					}
+
+					// thank rules
+					const rulesText = (
+						form.querySelector("#rules") as HTMLTextAreaElement
+					).value;
+					let rules: ThankRule[] = config ? config.rules : [];
+					if (rulesText) {
+						try {
+							rules = JSON.parse(rulesText);
+						} catch {
+							alert("规则不是合法的JSON");
+							return;
+						}
+					}
+
 					const newConfig: GiftThankConfig = {
 						open,
 						template,
+						aggregate,
+						aggregate_window_secs:
+							windowSecs || (config ? config.aggregate_window_secs : 5),
+						max_thanks_per_window:
+							maxThanks || (config ? config.max_thanks_per_window : 3),
+						summary_template,
+						rules,
 					};
 
 					await submitSettingChange(newConfig);
@@ -99,6 +173,100 @@ const GiftSettingsPanel = () => {
 					placeholder={placeholder_text}
 				></textarea>
 				<br />
+				<button
+					className="btn-primary"
+					type="button"
+					onClick={() => preview("template", "Gift")}
+				>
+					预览
+				</button>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="aggregate"
+				>
+					延时汇总感谢
+				</label>
+				<input type="checkbox" id="aggregate" />
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前：{config && config.aggregate ? "打开" : "关闭"}
+				</p>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="window"
+				>
+					汇总时长(秒):{" "}
+				</label>
+				<input
+					type="number"
+					id="window"
+					min={1}
+					placeholder={config ? `${config.aggregate_window_secs}` : ""}
+				/>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="max_thanks"
+				>
+					每段时间最多感谢数:{" "}
+				</label>
+				<input
+					type="number"
+					id="max_thanks"
+					min={1}
+					placeholder={config ? `${config.max_thanks_per_window}` : ""}
+				/>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="summary_template"
+				>
+					汇总感谢弹幕模版:{" "}
+				</label>
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前模版：{config ? config.summary_template : "未设置"}
+				</p>
+				<br />
+				<textarea
+					id="summary_template"
+					name="summary_template"
+					rows={6}
+					cols={60}
+					placeholder={summary_placeholder_text}
+				></textarea>
+				<br />
+				<button
+					className="btn-primary"
+					type="button"
+					onClick={() => preview("summary_template", "GiftSummary")}
+				>
+					预览
+				</button>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="rules"
+				>
+					感谢规则:{" "}
+				</label>
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前规则：{config ? JSON.stringify(config.rules) : "未设置"}
+				</p>
+				<br />
+				<textarea
+					id="rules"
+					name="rules"
+					rows={6}
+					cols={60}
+					placeholder={rules_placeholder_text}
+				></textarea>
+				<br />
 				<button className="btn-primary" value="submit">
 					提交设置
 				</button>
@@ -0,0 +1,200 @@
+import { useCallback, useEffect, useState } from "react";
+import { configurePlugin, listPlugins, previewTemplate } from "../apis/api";
+import { GuardScThankConfig } from "../bindings/GuardScThankConfig";
+import { TemplateEvent } from "../bindings/TemplateEvent";
+
+const PLUGIN_NAME = "guard_sc_thanker";
+
+const guard_placeholder_text = `示例模版：
+感谢{ uname }{ action }了{ months }个月{ guard_name }~
+目前可用的上舰macro:
+{uname}: 用户名
+{action}: 开通或续费
+{months}: 月数
+{guard_name}: 舰长, 提督或总督
+{price}: 价格(元)
+`;
+
+const super_chat_placeholder_text = `示例模版：
+感谢{ uname }的{ price }元醒目留言: { excerpt }
+目前可用的醒目留言macro:
+{uname}: 用户名
+{price}: 价格(元)
+{message}: 留言内容
+{excerpt}: 留言摘要
+`;
+
+// render the template in the textarea with a sample event
+const preview = async (textareaId: string, event: TemplateEvent) => {
+	const template = (document.getElementById(textareaId) as HTMLTextAreaElement)
+		.value;
+	if (!template) {
+		return;
+	}
+	const res = await previewTemplate({ template, event });
+	if (!res.success || res.payload === null) {
+		alert("预览失败");
+	} else if (res.payload.error !== null) {
+		const { msg, line, column } = res.payload.error;
+		alert(`模版错误 (第${line}行, 第${column}列): ${msg}`);
+	} else {
+		alert(`预览: ${res.payload.preview}`);
+	}
+};
+
+const GuardScSettingsPanel = () => {
+	const [config, setConfig] = useState<GuardScThankConfig>(null);
+
+	useEffect(() => {
+		const queryConfig = async () => {
+			const res = await listPlugins();
+			const plugin = res.payload?.find((p) => p.name === PLUGIN_NAME);
+			if (plugin) {
+				setConfig(plugin.config);
+			}
+		};
+		queryConfig();
+	}, []);
+
+	const submitSettingChange = useCallback(
+		async (config: GuardScThankConfig) => {
+			const res = await configurePlugin(PLUGIN_NAME, config);
+			if (res.success) {
+				alert("修改成功");
+				setConfig(config);
+			} else {
+				alert("修改失败");
+			}
+		},
+		[setConfig]
+	);
+
+	return (
+		<div className="self-stretch border border-cyan-400 p-2">
+			<h1 className="shadowed-text text-cyan-100 text-xl">
+				上舰/醒目留言感谢姬设置:
+			</h1>
+			<form
+				onSubmit={async (e) => {
+					e.preventDefault();
+					const form = e.target as HTMLFormElement;
+					const checked = (id: string) =>
+						(form.querySelector(`#${id}`) as HTMLInputElement).checked;
+					const text = (id: string) =>
+						(form.querySelector(`#${id}`) as HTMLTextAreaElement).value;
+
+					const excerptLen = parseInt(text("excerpt_len"));
+					const newConfig: GuardScThankConfig = {
+						guard_open: checked("guard_open"),
+						guard_template:
+							text("guard_template") || (config ? config.guard_template : ""),
+						super_chat_open: checked("super_chat_open"),
+						super_chat_template:
+							text("super_chat_template") ||
+							(config ? config.super_chat_template : ""),
+						excerpt_len: excerptLen || (config ? config.excerpt_len : 10),
+					};
+
+					await submitSettingChange(newConfig);
+				}}
+			>
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="guard_open"
+				>
+					感谢上舰
+				</label>
+				<input type="checkbox" id="guard_open" />
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前：{config && config.guard_open ? "打开" : "关闭"}
+				</p>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="guard_template"
+				>
+					上舰感谢弹幕模版:{" "}
+				</label>
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前模版：{config ? config.guard_template : "未设置"}
+				</p>
+				<br />
+				<textarea
+					id="guard_template"
+					name="guard_template"
+					rows={6}
+					cols={60}
+					placeholder={guard_placeholder_text}
+				></textarea>
+				<br />
+				<button
+					className="btn-primary"
+					type="button"
+					onClick={() => preview("guard_template", "GuardPurchase")}
+				>
+					预览
+				</button>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="super_chat_open"
+				>
+					感谢醒目留言
+				</label>
+				<input type="checkbox" id="super_chat_open" />
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前：{config && config.super_chat_open ? "打开" : "关闭"}
+				</p>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="super_chat_template"
+				>
+					醒目留言感谢弹幕模版:{" "}
+				</label>
+				<p className="text-md before:content-['('] after:content-[')']">
+					当前模版：{config ? config.super_chat_template : "未设置"}
+				</p>
+				<br />
+				<textarea
+					id="super_chat_template"
+					name="super_chat_template"
+					rows={6}
+					cols={60}
+					placeholder={super_chat_placeholder_text}
+				></textarea>
+				<br />
+				<button
+					className="btn-primary"
+					type="button"
+					onClick={() => preview("super_chat_template", "SuperChat")}
+				>
+					预览
+				</button>
+				<br />
+
+				<label
+					className="text-cyan-100 shadowed-text mr-2"
+					htmlFor="excerpt_len"
+				>
+					留言摘要字数:{" "}
+				</label>
+				<input
+					type="number"
+					id="excerpt_len"
+					min={1}
+					placeholder={config ? `${config.excerpt_len}` : ""}
+				/>
+				<br />
+				<button className="btn-primary" value="submit">
+					提交设置
+				</button>
+			</form>
+		</div>
+	);
+};
+
+export default GuardScSettingsPanel;
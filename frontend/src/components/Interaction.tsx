@@ -0,0 +1,36 @@
+import { InteractionMessage } from "../bindings/InteractionMessage";
+
+type InteractionProp = {
+	interaction: InteractionMessage;
+};
+
+const actions = {
+	Enter: "进入直播间",
+	Follow: "关注了主播",
+	Share: "分享了直播间",
+	SpecialFollow: "特别关注了主播",
+	MutualFollow: "与主播互相关注了",
+};
+
+/// 进场 & 关注 & 分享消息
+const Interaction = ({ interaction }: InteractionProp) => {
+	return (
+		<div className="flex flex-wrap h-full min-h-fit animate-danmaku-movein">
+			{interaction.medal ? (
+				<div className="inline-flex text-xs text-white border rounded-md border-black min-h-fit mx-px bg-gradient-to-r from-cyan-500 to-blue-500">
+					<span className="mx-px">{Number(interaction.medal.level)}</span>
+					<span className="inline-block border-l mx-px my-0 py-0 border-black"></span>
+					<span className="mx-px">{interaction.medal.name}</span>
+				</div>
+			) : null}
+			<span className="text-cyan-200 shadowed-text mx-px">
+				{interaction.uname}
+			</span>
+			<span className="text-white shadowed-text mx-px">
+				{actions[interaction.kind]}
+			</span>
+		</div>
+	);
+};
+
+export default Interaction;
@@ -0,0 +1,57 @@
+import { AnchorLotteryMessage } from "../bindings/AnchorLotteryMessage";
+import { LotteryWinner } from "../bindings/LotteryWinner";
+import { RedPocketMessage } from "../bindings/RedPocketMessage";
+
+type LotteryProp = {
+	title: string;
+	danmu?: string;
+	detail?: string;
+	winners?: Array<LotteryWinner>;
+};
+
+/// 红包 & 天选时刻
+const Lottery = ({ title, danmu, detail, winners }: LotteryProp) => {
+	return (
+		<div className="flex flex-wrap h-full min-h-fit animate-danmaku-movein">
+			<span className="text-rose-400 shadowed-text mx-px">{title}</span>
+			{detail ? (
+				<span className="text-white shadowed-text mx-px">{detail}</span>
+			) : null}
+			{danmu ? (
+				<span className="text-yellow-200 shadowed-text mx-px">
+					{`口令: ${danmu}`}
+				</span>
+			) : null}
+			{winners
+				? winners.map((winner) => (
+						<span
+							key={winner.uid}
+							className="text-cyan-200 shadowed-text mx-px"
+						>
+							{`${winner.uname}(${winner.award_name})`}
+						</span>
+				  ))
+				: null}
+		</div>
+	);
+};
+
+const RedPocket = ({ redPocket }: { redPocket: RedPocketMessage }) => (
+	<Lottery
+		title={`${redPocket.sender_name}的红包`}
+		detail={redPocket.awards
+			.map((award) => `${award.num}个${award.gift_name}`)
+			.join(" ")}
+		danmu={redPocket.danmu}
+	/>
+);
+
+const AnchorLottery = ({ lottery }: { lottery: AnchorLotteryMessage }) => (
+	<Lottery
+		title="天选时刻"
+		detail={`${lottery.award_num}个${lottery.award_name} ${lottery.require_text}`}
+		danmu={lottery.danmu}
+	/>
+);
+
+export { Lottery, RedPocket, AnchorLottery };
@@ -2,6 +2,8 @@ import React, { ForwardedRef } from "react";
 import { BiliMessage } from "../bindings/BiliMessage";
 import Danmu from "./Danmu";
 import Gift from "./Gift";
+import Interaction from "./Interaction";
+import { AnchorLottery, Lottery, RedPocket } from "./Lottery";
 
 declare interface MessageProp {
 	message: BiliMessage;
@@ -20,6 +22,16 @@ const Message = React.forwardRef<HTMLDivElement, MessageProp>(function Message(
 				<Danmu danmu={message.body} />
 			) : message.type === "Gift" ? (
 				<Gift gift={message.body} />
+			) : message.type === "Interaction" ? (
+				<Interaction interaction={message.body} />
+			) : message.type === "RedPocketStart" ? (
+				<RedPocket redPocket={message.body} />
+			) : message.type === "RedPocketWinners" ? (
+				<Lottery title="红包中奖" winners={message.body.winners} />
+			) : message.type === "AnchorLotteryStart" ? (
+				<AnchorLottery lottery={message.body} />
+			) : message.type === "AnchorLotteryAward" ? (
+				<Lottery title="天选中奖" winners={message.body.winners} />
 			) : null}
 		</div>
 	);
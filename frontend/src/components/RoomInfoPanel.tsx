@@ -81,7 +81,8 @@ const RoomInfoPanel = ({
 				<div>
 					<h1>当前连接到房间: {room.roomid}</h1>
 					<h1>主播名称: {room.uname}</h1>
-					<h1>直播标题: {room.content}</h1>
+					<h1>直播标题: {room.title}</h1>
+					<h1>直播状态: {room.live_status === 1 ? "直播中" : "未开播"}</h1>
 					<button
 						className="btn-primary mt-1"
 						onClick={submitDisconnect}
@@ -1,6 +1,8 @@
 //! This module contains Danmuji's Web APIs
 
+pub mod plugins;
 pub mod room;
 pub mod settings;
+pub mod templates;
 pub mod user;
 pub mod ws;
@@ -0,0 +1,71 @@
+//! This module contains Danmuji's Web API for managing plugins
+
+use axum::{extract::Path, Extension, Json};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{plugins::PluginInfo, DanmujiApiResponse, DanmujiResult, DanmujiState};
+
+/// Request Path: <host>/api/plugins
+/// Request Method: GET
+///
+/// List all plugins with their configs and config schemas
+pub async fn listPlugins(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+) -> DanmujiResult<DanmujiApiResponse<Vec<PluginInfo>>> {
+  let state = state.lock().await;
+  Ok(DanmujiApiResponse::success(Some(
+    state.plugins.list().await,
+  )))
+}
+
+/// Request Path: <host>/api/plugins/:name/enable
+/// Request Method: POST
+///
+/// Start the plugin
+///
+/// # Failure:
+/// Fails if the plugin doesn't exist or fails to start
+pub async fn enablePlugin(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+  Path(name): Path<String>,
+) -> DanmujiResult<DanmujiApiResponse<()>> {
+  let state = state.lock().await;
+  state.plugins.enable(&name).await?;
+  Ok(DanmujiApiResponse::success(None))
+}
+
+/// Request Path: <host>/api/plugins/:name/disable
+/// Request Method: POST
+///
+/// Stop the plugin
+///
+/// # Failure:
+/// Fails if the plugin doesn't exist
+pub async fn disablePlugin(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+  Path(name): Path<String>,
+) -> DanmujiResult<DanmujiApiResponse<()>> {
+  let state = state.lock().await;
+  state.plugins.disable(&name).await?;
+  Ok(DanmujiApiResponse::success(None))
+}
+
+/// Request Path: <host>/api/plugins/:name/config
+/// Request Method: POST
+/// Request Body: Json, following the plugin's config schema
+///
+/// Change and persist the plugin's config
+///
+/// # Failure:
+/// Fails if the plugin doesn't exist or rejects the config
+pub async fn configurePlugin(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+  Path(name): Path<String>,
+  Json(config): Json<Value>,
+) -> DanmujiResult<DanmujiApiResponse<()>> {
+  let state = state.lock().await;
+  state.plugins.configure(&name, config).await?;
+  Ok(DanmujiApiResponse::success(None))
+}
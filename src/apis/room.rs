@@ -1,11 +1,17 @@
 //! Modules for room connection/disconnection APIs
 
-use axum::{extract::Path, Extension};
-use std::sync::Arc;
+use axum::{
+  extract::{Path, Query},
+  Extension,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::Mutex;
 use tracing::warn;
+use ts_rs::TS;
 
 use crate::{
+  client::{DedupStats, PkSession, Transport},
   config::{Room, RoomConfig},
   util::{delete_room_config, save_room_config},
   DanmujiApiResponse, DanmujiResult, DanmujiState,
@@ -31,6 +37,125 @@ pub async fn getRoomStatus(
   }
 }
 
+/// Heartbeat Round-trip Time of the Connected Room
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/RoomLatency.ts")]
+pub struct RoomLatency {
+  #[ts(type = "number")]
+  room_id: i64,
+  // None until the server answers a heartbeat
+  latency_ms: Option<u32>,
+}
+
+/// Request Path: <host>/api/roomLatency
+/// Request Method: GET
+///
+/// Query the heartbeat round-trip time of the connected room
+///
+/// # Failure:
+/// Fails if no room is connected
+pub async fn getRoomLatency(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+) -> DanmujiResult<DanmujiApiResponse<RoomLatency>> {
+  let state = state.lock().await;
+
+  let Some(room_config) = &state.room else {
+    return Ok(DanmujiApiResponse::failure(None));
+  };
+  let room_id = room_config.room_init.room_id;
+  let latency_ms = state
+    .cli
+    .latency(room_id)
+    .map(|latency| latency.as_millis().try_into().unwrap_or(u32::MAX));
+
+  Ok(DanmujiApiResponse::success(Some(RoomLatency {
+    room_id,
+    latency_ms,
+  })))
+}
+
+/// Notification Commands of the Connected Room without a Typed Message
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/UnhandledCommands.ts")]
+pub struct UnhandledCommands {
+  #[ts(type = "number")]
+  room_id: i64,
+  // cmd -> number of notifications received
+  #[ts(type = "Record<string, number>")]
+  counts: HashMap<String, u64>,
+}
+
+/// Request Path: <host>/api/unhandledCommands
+/// Request Method: GET
+///
+/// Query how many notifications of each command without a typed
+/// message the connected room has received
+///
+/// # Failure:
+/// Fails if no room is connected
+pub async fn getUnhandledCommands(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+) -> DanmujiResult<DanmujiApiResponse<UnhandledCommands>> {
+  let state = state.lock().await;
+
+  let Some(room_config) = &state.room else {
+    return Ok(DanmujiApiResponse::failure(None));
+  };
+  let room_id = room_config.room_init.room_id;
+  let counts = state.cli.unhandled_commands(room_id).unwrap_or_default();
+
+  Ok(DanmujiApiResponse::success(Some(UnhandledCommands {
+    room_id,
+    counts,
+  })))
+}
+
+/// Request Path: <host>/api/roomPk
+/// Request Method: GET
+///
+/// Query the latest PK battle of the connected room, the payload is null
+/// if no PK has been seen since the room was connected
+///
+/// # Failure:
+/// Fails if no room is connected
+pub async fn getRoomPk(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+) -> DanmujiResult<DanmujiApiResponse<PkSession>> {
+  let state = state.lock().await;
+
+  let Some(room_config) = &state.room else {
+    return Ok(DanmujiApiResponse::failure(None));
+  };
+  let pk = state.cli.pk(room_config.room_init.room_id);
+
+  Ok(DanmujiApiResponse::success(pk))
+}
+
+/// Request Path: <host>/api/dedupStats
+/// Request Method: GET
+///
+/// Query how many duplicate messages of the connected room have been dropped
+///
+/// # Failure:
+/// Fails if no room is connected
+pub async fn getDedupStats(
+  Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
+) -> DanmujiResult<DanmujiApiResponse<DedupStats>> {
+  let state = state.lock().await;
+
+  let Some(room_config) = &state.room else {
+    return Ok(DanmujiApiResponse::failure(None));
+  };
+  let stats = state
+    .cli
+    .dedup_stats(room_config.room_init.room_id)
+    .unwrap_or_default();
+
+  Ok(DanmujiApiResponse::success(Some(stats)))
+}
+
 /// Request Path: <host>/api/disconnect
 /// Request Method: POST
 ///
@@ -55,11 +180,19 @@ pub async fn disconnect(
   Ok(DanmujiApiResponse::success(None))
 }
 
-/// Request Path: <host>/api/roomInit/:room_id
+/// Query parameters of [roomInit]
+#[derive(Debug, Deserialize)]
+pub struct RoomInitQuery {
+  // preferred transport, ws or wss
+  #[serde(default)]
+  transport: Transport,
+}
+
+/// Request Path: <host>/api/roomInit/:room_id?transport=<ws|wss>
 /// Request Method: POST
 ///
 /// try to set up a websocket connection to the live room of specified
-/// id.
+/// id. The transport preference is optional and defaults to plain ws.
 ///
 ///
 /// # Error:
@@ -67,24 +200,34 @@ pub async fn disconnect(
 ///
 /// # Failure:
 /// Fails if (a). the given room_id is not a valid room
-/// or (b). we have already connected to a room.
+/// or (b). we have already connected to a room. A room whose
+/// connection has given up reconnecting is disconnected first.
 ///
 /// # Success:
 /// On success, client is connected to the specified room
 pub async fn roomInit(
   Path(room_id): Path<i64>,
+  Query(query): Query<RoomInitQuery>,
   Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
 ) -> DanmujiResult<DanmujiApiResponse<Room>> {
   // a room is already connected to
   let mut state = state.lock().await;
 
   // already connected
-  if state.room.is_some() {
-    return Ok(DanmujiApiResponse::failure(None));
+  if let Some(room) = &state.room {
+    let connected = room.room_init.room_id;
+    if !state.cli.is_stopped(connected) {
+      return Ok(DanmujiApiResponse::failure(None));
+    }
+    warn!("Room {} Has Stopped, Replacing It", connected);
+    state.room = None;
+    state.cli.disconnect(connected).await;
+    state.sender.disconnect_room().await;
   }
 
   // fetch room config
-  let room_config = RoomConfig::fetch(room_id).await?;
+  let mut room_config = RoomConfig::fetch(room_id).await?;
+  room_config.transport = query.transport;
   if room_config.room_init.room_id == 0 {
     // invalid room id
     return Ok(DanmujiApiResponse::failure(None));
@@ -97,6 +240,7 @@ pub async fn roomInit(
 
   let return_room = room_config.room.clone();
   let room_id = room_config.room_init.room_id;
+  let transport = room_config.transport;
   state.sender.connect_room(room_config.clone()).await?;
   state.room = Some(room_config);
 
@@ -104,7 +248,7 @@ pub async fn roomInit(
 
   // start client
   let cli = &mut state.cli;
-  cli.start(room_id, uid)?;
+  cli.start(room_id, uid, transport)?;
 
   // Ok
   Ok(DanmujiApiResponse::success(Some(return_room)))
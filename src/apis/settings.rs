@@ -4,11 +4,10 @@ use axum::{Extension, Json};
 use axum_macros::debug_handler;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tracing::warn;
 
 use crate::{
-  plugins::GiftThankConfig, util::save_thank_config, DanmujiApiResponse, DanmujiResult,
-  DanmujiState,
+  plugins::{parse_config, GiftThankConfig, GiftThanker},
+  DanmujiApiResponse, DanmujiResult, DanmujiState,
 };
 
 /// Request Path: <host>/api/getGiftConfig
@@ -19,24 +18,28 @@ pub async fn queryGiftConfig(
   Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
 ) -> DanmujiResult<DanmujiApiResponse<GiftThankConfig>> {
   let state = state.lock().await;
-  let config = state.thanker.get_config().await;
-  Ok(DanmujiApiResponse::success(config))
+  let config = state.plugins.config(GiftThanker::NAME).await?;
+  Ok(DanmujiApiResponse::success(Some(parse_config(config)?)))
 }
 
 /// Request Path <host>/api/setGiftConfig
 /// Request Method: POST
 /// Request Body: Json<GiftThankConfig>
 ///
-/// set server's gift thank config
+/// set server's gift thank config, same as configuring the gift thanker plugin
+///
+/// # Failure:
+/// Fails if a template doesn't compile, including the templates of the rules,
+/// or the aggregation settings are not positive
 #[debug_handler]
 pub async fn setGiftConfig(
   Extension(state): Extension<Arc<Mutex<DanmujiState>>>,
   Json(config): Json<GiftThankConfig>,
 ) -> DanmujiResult<DanmujiApiResponse<()>> {
-  if let Err(err) = save_thank_config(&config) {
-    warn!("Fail Saving Thank Config: {}", err);
-  }
   let state = state.lock().await;
-  state.thanker.set_config(config).await;
+  state
+    .plugins
+    .configure(GiftThanker::NAME, serde_json::to_value(config)?)
+    .await?;
   Ok(DanmujiApiResponse::success(None))
 }
@@ -0,0 +1,71 @@
+//! This module contains Danmuji's Web API for checking thank templates
+//! before they are put to use
+
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+  plugins::template::{preview, CompiledTemplate, TemplateError, TemplateEvent},
+  DanmujiApiResponse, DanmujiResult,
+};
+
+/// A template and the kind of event it is rendered with
+#[derive(Debug, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/TemplateRequest.ts")]
+pub struct TemplateRequest {
+  template: String,
+  event: TemplateEvent,
+}
+
+/// Result of validating a template
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/TemplateValidation.ts")]
+pub struct TemplateValidation {
+  // None if the template compiles
+  error: Option<TemplateError>,
+  // variables of the event the template can use
+  variables: Vec<String>,
+}
+
+/// Result of previewing a template
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/TemplatePreview.ts")]
+pub struct TemplatePreview {
+  // the template rendered with a sample event, None on error
+  preview: Option<String>,
+  error: Option<TemplateError>,
+}
+
+/// Request Path: <host>/api/templates/validate
+/// Request Method: POST
+/// Request Body: Json<TemplateRequest>
+///
+/// Compile the template and list the variables of its event
+pub async fn validateTemplate(
+  Json(request): Json<TemplateRequest>,
+) -> DanmujiResult<DanmujiApiResponse<TemplateValidation>> {
+  Ok(DanmujiApiResponse::success(Some(TemplateValidation {
+    error: CompiledTemplate::compile(&request.template).err(),
+    variables: request.event.variables(),
+  })))
+}
+
+/// Request Path: <host>/api/templates/preview
+/// Request Method: POST
+/// Request Body: Json<TemplateRequest>
+///
+/// Render the template with a sample event, which also reports
+/// the variables the event doesn't have
+pub async fn previewTemplate(
+  Json(request): Json<TemplateRequest>,
+) -> DanmujiResult<DanmujiApiResponse<TemplatePreview>> {
+  let result = preview(&request.template, request.event);
+  Ok(DanmujiApiResponse::success(Some(TemplatePreview {
+    preview: result.as_ref().ok().cloned(),
+    error: result.err(),
+  })))
+}
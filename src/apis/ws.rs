@@ -12,7 +12,7 @@ use axum::{
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
 use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
@@ -42,7 +42,17 @@ async fn handle_socket(socket: WebSocket, state: Arc<Mutex<DanmujiState>>) {
 
   // This task will receive incoming BiliMessages and forward to client
   let mut send_task = tokio::spawn(async move {
-    while let Ok(msg) = rx.recv().await {
+    loop {
+      let msg = match rx.recv().await {
+        Ok(msg) => msg,
+        // audience counts come often, a slow client skips some messages
+        // rather than losing the connection
+        Err(RecvError::Lagged(skipped)) => {
+          warn!("Websocket Client Lagged, {} Messages Skipped", skipped);
+          continue;
+        }
+        Err(RecvError::Closed) => break,
+      };
       // In any websocket error, break loop.
       if sender
         .send(Message::Text(serde_json::to_string(&msg).unwrap()))
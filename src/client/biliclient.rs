@@ -6,29 +6,211 @@
 //! - Forwarding the converted structure to the downstream consumers
 
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
+  convert::Infallible,
+  path::{Path, PathBuf},
+  str::FromStr,
   sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
   },
-  time::Duration,
+  time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use futures::Stream;
 use futures::{SinkExt, StreamExt};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message, tungstenite::Error};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpStream, time::Instant};
+use tokio_tungstenite::{
+  connect_async_tls_with_config, tungstenite::protocol::Message, Connector, MaybeTlsStream,
+  WebSocketStream,
+};
 use tracing::{error, info, trace, warn};
 
-use crate::DanmujiResult;
+use crate::{config::WsConfig, DanmujiResult};
 
-use super::{common::BiliMessage, message::BiliWebsocketMessage};
+use super::{
+  common::{BiliMessage, PkEvent, PkSession},
+  message::{BiliWebsocketMessage, PROTO_BROTLI},
+  pipeline::{publish, DedupStats, Pipeline},
+  recording::{read_recording, RecordedFrame, Recorder, ReplaySpeed},
+};
 
-// Bilibili's Websocket URL
+// Bilibili's Websocket URLs, used when the room's server list is not available
 const URL: &str = "ws://broadcastlv.chat.bilibili.com:2244/sub";
+const TLS_URL: &str = "wss://broadcastlv.chat.bilibili.com:443/sub";
+// Bilibili's live API
+const API_BASE: &str = "https://api.live.bilibili.com";
+// how often a sleeping worker checks whether it is shut down
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 // consumer type
 pub type Consumer = tokio::sync::broadcast::Sender<BiliMessage>;
 
+/// Options shared by all the connections started by a [BiliClient]
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+  /// protocol version sent in the entry packet, which decides
+  /// how the server compresses notifications ([PROTO_ZLIB](super::message::PROTO_ZLIB)
+  /// or [PROTO_BROTLI])
+  pub protover: u16,
+  /// base url of Bilibili's live API, where the websocket server list is fetched
+  pub api_base: String,
+  /// TLS connector for wss connections, None to use the system's default
+  pub tls_connector: Option<native_tls::TlsConnector>,
+  /// reconnection policy
+  pub backoff: Backoff,
+  /// keepalive policy
+  pub heartbeat: Heartbeat,
+  /// directory to record the raw frames of every connection to,
+  /// None to disable recording
+  pub record_dir: Option<PathBuf>,
+  /// notifications of unknown commands forwarded as [BiliMessage::Raw]
+  pub raw_passthrough: RawPassthrough,
+  /// how long the ids of danmu, gifts and super chats are remembered to drop
+  /// the messages pushed again, zero to forward duplicates
+  pub dedup_window: Duration,
+  /// whether viewers entering the room are forwarded as interactions,
+  /// turned off in busy rooms where they are the bulk of the messages
+  pub forward_enters: bool,
+}
+
+impl Default for ClientOptions {
+  fn default() -> Self {
+    Self {
+      protover: PROTO_BROTLI,
+      api_base: API_BASE.to_string(),
+      tls_connector: None,
+      backoff: Backoff::default(),
+      heartbeat: Heartbeat::default(),
+      record_dir: None,
+      raw_passthrough: RawPassthrough::default(),
+      dedup_window: Duration::from_secs(60),
+      forward_enters: true,
+    }
+  }
+}
+
+/// Which notifications of commands without a typed [BiliMessage]
+/// variant are forwarded as [BiliMessage::Raw]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RawPassthrough {
+  /// none of them
+  #[default]
+  Off,
+  /// only these commands
+  Cmds(HashSet<String>),
+  /// all of them
+  All,
+}
+
+impl RawPassthrough {
+  /// whether notifications of the command are forwarded
+  pub fn forwards(&self, cmd: &str) -> bool {
+    match self {
+      RawPassthrough::Off => false,
+      RawPassthrough::Cmds(cmds) => cmds.contains(cmd),
+      RawPassthrough::All => true,
+    }
+  }
+}
+
+/// parse from "*" for all commands, or a comma separated list
+/// of commands like "STOP_LIVE_ROOM_LIST,HOT_RANK_CHANGED"
+impl FromStr for RawPassthrough {
+  type Err = Infallible;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let cmds: HashSet<String> = s
+      .split(',')
+      .map(str::trim)
+      .filter(|cmd| !cmd.is_empty())
+      .map(String::from)
+      .collect();
+    Ok(if cmds.contains("*") {
+      RawPassthrough::All
+    } else if cmds.is_empty() {
+      RawPassthrough::Off
+    } else {
+      RawPassthrough::Cmds(cmds)
+    })
+  }
+}
+
+/// Keepalive policy of a room's connection. A connection that receives
+/// neither a heartbeat reply nor a notification within `timeout` is
+/// considered dead and reconnected
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+  /// interval between heartbeats
+  pub interval: Duration,
+  /// how long to wait for the server before reconnecting
+  pub timeout: Duration,
+}
+
+impl Default for Heartbeat {
+  fn default() -> Self {
+    Self {
+      interval: Duration::from_secs(20),
+      timeout: Duration::from_secs(60),
+    }
+  }
+}
+
+/// Reconnection policy of a room's connection. The delay before
+/// each retry grows exponentially from `initial` up to `max`, with
+/// random jitter so that clients don't retry in lockstep
+#[derive(Debug, Clone)]
+pub struct Backoff {
+  /// delay before the first retry
+  pub initial: Duration,
+  /// cap of the delay
+  pub max: Duration,
+  /// give up after this many consecutive retries, None to retry forever
+  pub max_retries: Option<u32>,
+}
+
+impl Default for Backoff {
+  fn default() -> Self {
+    Self {
+      initial: Duration::from_secs(1),
+      max: Duration::from_secs(60),
+      max_retries: None,
+    }
+  }
+}
+
+impl Backoff {
+  /// delay before the given retry (counting from 0), drawn uniformly
+  /// from the upper half of the exponential delay
+  fn delay(&self, retry: u32) -> Duration {
+    let exponential = self
+      .initial
+      .saturating_mul(2u32.saturating_pow(retry))
+      .min(self.max);
+    let half = exponential / 2;
+    half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+  }
+
+  /// whether to give up after the given number of consecutive failures
+  fn gives_up(&self, failures: u32) -> bool {
+    self.max_retries.is_some_and(|max| failures > max)
+  }
+}
+
+/// Transport used to connect to Bilibili's websocket servers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+  /// plain websocket (ws://)
+  #[default]
+  Ws,
+  /// websocket over TLS (wss://), falls back to plain
+  /// websocket if none of the TLS servers can be connected
+  Wss,
+}
+
 /// The [BiliClient] struct that represents a handle and manager to a
 /// pool of background tasks that connect to & interact with BiliBili's
 /// live room websocket servers.
@@ -37,65 +219,246 @@ pub type Consumer = tokio::sync::broadcast::Sender<BiliMessage>;
 /// broadcast Sender, where it will forward everything.
 #[derive(Debug)]
 pub struct BiliClient {
-  // connected_room_id -> shutdown flag
-  shutdown: HashMap<i64, Arc<AtomicBool>>,
+  // connected_room_id -> status of the connection
+  rooms: HashMap<i64, Arc<RoomStatus>>,
   // connected_room_id -> JoinHandle that runs the connection
   tasks: HashMap<i64, tokio::task::JoinHandle<()>>,
   // downstream consumer of the client
   downstream: Consumer,
+  // options applied to every started connection
+  options: ClientOptions,
+}
+
+/// Status of a room's connection, shared between the [BiliClient]
+/// handle and the background task that runs the connection
+#[derive(Debug, Default)]
+pub(super) struct RoomStatus {
+  // set by the handle to signal termination
+  shutdown: AtomicBool,
+  // set by the task when it stops on its own, i.e. it gives up
+  // reconnecting or finishes replaying
+  stopped: AtomicBool,
+  // when the pending heartbeat was sent
+  heartbeat_sent: Mutex<Option<Instant>>,
+  // round-trip time of the last answered heartbeat
+  latency: Mutex<Option<Duration>>,
+  // cmd -> number of notifications received without a typed variant
+  unhandled: Mutex<HashMap<String, u64>>,
+  // the latest PK of the room
+  pk: Mutex<Option<PkSession>>,
+  // messages checked & dropped by the dedup stage
+  dedup: Mutex<DedupStats>,
+}
+
+impl RoomStatus {
+  fn heartbeat_sent(&self) {
+    *self.heartbeat_sent.lock().unwrap() = Some(Instant::now());
+  }
+
+  pub(super) fn heartbeat_replied(&self) {
+    if let Some(sent) = self.heartbeat_sent.lock().unwrap().take() {
+      *self.latency.lock().unwrap() = Some(sent.elapsed());
+    }
+  }
+
+  pub(super) fn count_unhandled(&self, cmd: &str) {
+    let mut unhandled = self.unhandled.lock().unwrap();
+    match unhandled.get_mut(cmd) {
+      Some(count) => *count += 1,
+      None => {
+        unhandled.insert(cmd.to_string(), 1);
+      }
+    }
+  }
+
+  pub(super) fn unhandled(&self) -> HashMap<String, u64> {
+    self.unhandled.lock().unwrap().clone()
+  }
+
+  pub(super) fn update_pk(&self, room_id: i64, event: &PkEvent) {
+    PkSession::update(&mut self.pk.lock().unwrap(), room_id as u64, event);
+  }
+
+  pub(super) fn count_dedup(&self, msg: &BiliMessage, duplicate: bool) {
+    self.dedup.lock().unwrap().count(msg, duplicate);
+  }
+
+  pub(super) fn dedup_stats(&self) -> DedupStats {
+    self.dedup.lock().unwrap().clone()
+  }
+
+  pub(super) fn pk(&self) -> Option<PkSession> {
+    self.pk.lock().unwrap().clone()
+  }
+
+  fn reset_latency(&self) {
+    *self.heartbeat_sent.lock().unwrap() = None;
+    *self.latency.lock().unwrap() = None;
+  }
 }
 
 impl BiliClient {
   /// Create a Client instance and bind to the given consumer
   ///
   /// * `downstream` downstream consumer of the messages
+  #[cfg(test)]
   pub fn new(downstream: Consumer) -> Self {
+    Self::with_options(downstream, ClientOptions::default())
+  }
+
+  /// Create a Client instance with the given options and bind to the given consumer
+  ///
+  /// * `downstream` downstream consumer of the messages
+  /// * `options` options applied to every connection started by this client
+  pub fn with_options(downstream: Consumer, options: ClientOptions) -> Self {
     Self {
-      shutdown: HashMap::new(),
+      rooms: HashMap::new(),
       tasks: HashMap::new(),
       downstream,
+      options,
     }
   }
 
   /// Start a [BiliClient] instance that connects to specified room
   /// as the given user.
   /// This method can safely be called many times to connect to multiple live rooms.
+  /// A room whose connection has stopped, e.g. it gave up reconnecting, is restarted.
   ///
   /// * `room_id` id of the connected room
   /// * `user_id` id of user, 0 if not provided
+  /// * `transport` preferred transport of the connection
   ///
   /// # Note:
   /// When user_id is 0, websocket sometimes fails to receive meaningful messages, so it
   /// is recommended that a valid user_id be provided
-  pub fn start(&mut self, room_id: i64, user_id: Option<u64>) -> DanmujiResult<()> {
-    if self.shutdown.contains_key(&room_id) {
+  pub fn start(
+    &mut self,
+    room_id: i64,
+    user_id: Option<u64>,
+    transport: Transport,
+  ) -> DanmujiResult<()> {
+    if self.is_running(room_id) {
       return Ok(());
     }
 
     // the control signal for the room_id
-    let shutdown = Arc::new(AtomicBool::new(false));
+    let status = Arc::new(RoomStatus::default());
     let task = {
-      let shutdown = shutdown.clone();
+      let status = status.clone();
       let downstream = self.downstream.clone();
       let config = ClientConfig {
         room_id,
         user_id,
-        shutdown,
+        protover: self.options.protover,
+        api_base: self.options.api_base.clone(),
+        transport,
+        tls_connector: self.options.tls_connector.clone(),
+        backoff: self.options.backoff.clone(),
+        heartbeat: self.options.heartbeat.clone(),
+        record_dir: self.options.record_dir.clone(),
+        raw_passthrough: self.options.raw_passthrough.clone(),
+        dedup_window: self.options.dedup_window,
+        forward_enters: self.options.forward_enters,
+        status,
         downstream,
       };
 
-      tokio::spawn(start_worker(config, URL))
+      tokio::spawn(start_worker(config))
     };
 
-    self.shutdown.insert(room_id, shutdown);
+    self.rooms.insert(room_id, status);
     self.tasks.insert(room_id, task);
     Ok(())
   }
 
+  /// Replay a recorded session in place of a live connection to the specified room.
+  /// Frames go through the same parsing as live ones and are forwarded to the
+  /// downstream consumer. Like [BiliClient::start], this does nothing if the room
+  /// is running.
+  ///
+  /// * `room_id` id of the room the messages are published for
+  /// * `path` recording file written by a client with [ClientOptions::record_dir]
+  /// * `speed` how fast the recording is replayed
+  ///
+  /// # Error:
+  /// Fails if the recording can't be read
+  pub fn replay(
+    &mut self,
+    room_id: i64,
+    path: impl AsRef<Path>,
+    speed: ReplaySpeed,
+  ) -> DanmujiResult<()> {
+    if self.is_running(room_id) {
+      return Ok(());
+    }
+
+    let path = path.as_ref();
+    let frames = read_recording(path)?;
+    let source = format!("file://{}", path.display());
+    let status = Arc::new(RoomStatus::default());
+    let pipeline = Pipeline::new(
+      room_id,
+      status.clone(),
+      self.downstream.clone(),
+      self.options.raw_passthrough.clone(),
+      self.options.dedup_window,
+      self.options.forward_enters,
+    );
+    let task = tokio::spawn(start_replay(frames, speed, source, pipeline));
+
+    self.rooms.insert(room_id, status);
+    self.tasks.insert(room_id, task);
+    Ok(())
+  }
+
+  // whether a task is running for the room
+  fn is_running(&self, room_id: i64) -> bool {
+    self
+      .rooms
+      .get(&room_id)
+      .is_some_and(|status| !status.stopped.load(Ordering::Relaxed))
+  }
+
+  /// Whether the connection to the room has stopped on its own, e.g. it
+  /// gave up reconnecting, false if the room is not connected
+  pub fn is_stopped(&self, room_id: i64) -> bool {
+    self
+      .rooms
+      .get(&room_id)
+      .is_some_and(|status| status.stopped.load(Ordering::Relaxed))
+  }
+
+  /// Round-trip time of the last heartbeat answered in the specified room,
+  /// None if the room is not connected or no heartbeat has been answered yet
+  pub fn latency(&self, room_id: i64) -> Option<Duration> {
+    let status = self.rooms.get(&room_id)?;
+    let latency = *status.latency.lock().unwrap();
+    latency
+  }
+
+  /// Number of notifications received in the specified room for each command
+  /// without a typed variant, whether forwarded or not. None if the room is not connected
+  pub fn unhandled_commands(&self, room_id: i64) -> Option<HashMap<String, u64>> {
+    let status = self.rooms.get(&room_id)?;
+    Some(status.unhandled())
+  }
+
+  /// The latest PK battle of the specified room, None if the room is not
+  /// connected or no PK has been seen since it was
+  pub fn pk(&self, room_id: i64) -> Option<PkSession> {
+    self.rooms.get(&room_id)?.pk()
+  }
+
+  /// Statistics of the duplicates dropped in the specified room,
+  /// None if the room is not connected
+  pub fn dedup_stats(&self, room_id: i64) -> Option<DedupStats> {
+    Some(self.rooms.get(&room_id)?.dedup_stats())
+  }
+
   /// Disconnect from the specified room
   pub async fn disconnect(&mut self, room_id: i64) {
-    if let Some(shutdown) = self.shutdown.remove(&room_id) {
-      shutdown.store(true, Ordering::Relaxed);
+    if let Some(status) = self.rooms.remove(&room_id) {
+      status.shutdown.store(true, Ordering::Relaxed);
       let task = self.tasks.remove(&room_id);
       tokio::spawn(async move {
         if let Some(task) = task {
@@ -110,10 +473,10 @@ impl BiliClient {
 
   /// Shutdown this client, disconnecting from all rooms
   pub fn shutdown(&mut self) {
-    let ids = std::mem::take(&mut self.shutdown);
+    let rooms = std::mem::take(&mut self.rooms);
     let tasks = std::mem::take(&mut self.tasks);
-    for shutdown in ids.into_values() {
-      shutdown.store(true, Ordering::Relaxed);
+    for status in rooms.into_values() {
+      status.shutdown.store(true, Ordering::Relaxed);
     }
     tokio::spawn(async move {
       for task in tasks.into_values() {
@@ -139,112 +502,909 @@ impl Drop for BiliClient {
 struct ClientConfig {
   room_id: i64,
   user_id: Option<u64>,
-  // shared with the top-level Client Handle & will be modified
-  // by the handle to signal termination
-  shutdown: Arc<AtomicBool>,
+  // protocol version sent in the entry packet
+  protover: u16,
+  // base url of Bilibili's live API
+  api_base: String,
+  // preferred transport & the connector used for TLS
+  transport: Transport,
+  tls_connector: Option<native_tls::TlsConnector>,
+  // reconnection & keepalive policy
+  backoff: Backoff,
+  heartbeat: Heartbeat,
+  // where to record the session
+  record_dir: Option<PathBuf>,
+  // unknown commands to forward
+  raw_passthrough: RawPassthrough,
+  // how long message ids are remembered
+  dedup_window: Duration,
+  forward_enters: bool,
+  // shared with the top-level Client Handle, which will modify
+  // it to signal termination
+  status: Arc<RoomStatus>,
   downstream: Consumer,
 }
 
+/// Websocket servers of a room to rotate through,
+/// and the token to authenticate with
+#[derive(Debug, Default)]
+struct Endpoints {
+  token: String,
+  urls: Vec<String>,
+  // index of the next url to try
+  next: usize,
+}
+
+impl Endpoints {
+  /// fetch the room's server list, falling back to the default
+  /// server when the list is not available. With [Transport::Wss],
+  /// TLS urls come first and plain urls follow as the fallback
+  async fn fetch(api_base: &str, room_id: i64, transport: Transport) -> Self {
+    match WsConfig::fetch(api_base, room_id).await {
+      Ok(config) if !config.host_list.is_empty() => {
+        let urls = match transport {
+          Transport::Ws => config.ws_urls(),
+          Transport::Wss => [config.wss_urls(), config.ws_urls()].concat(),
+        };
+        Self {
+          urls,
+          token: config.token,
+          next: 0,
+        }
+      }
+      Ok(_) => {
+        warn!(
+          "Room {} Has Empty Server List, Using Default Server",
+          room_id
+        );
+        Self::fallback(transport)
+      }
+      Err(err) => {
+        warn!(
+          "Room {} Fail Fetching Server List: {}, Using Default Server",
+          room_id, err
+        );
+        Self::fallback(transport)
+      }
+    }
+  }
+
+  fn fallback(transport: Transport) -> Self {
+    let urls = match transport {
+      Transport::Ws => vec![URL.to_string()],
+      Transport::Wss => vec![TLS_URL.to_string(), URL.to_string()],
+    };
+    Self {
+      token: String::new(),
+      urls,
+      next: 0,
+    }
+  }
+
+  /// the next url to connect to, None if all of them have been tried
+  fn next_url(&mut self) -> Option<String> {
+    let url = self.urls.get(self.next).cloned();
+    self.next += 1;
+    url
+  }
+}
+
 /// Takes care of keeping the websocket connection alive in the background
 /// When not shut down, this function runs in an infinite read loop. If connection is broken
-/// by accident, it reconnects automatically
+/// by accident, it reconnects automatically with exponential backoff, until it gives up after
+/// too many consecutive failures. Servers are fetched from the room's server list,
+/// and the next one is tried when connecting fails.
+///
+/// Changes of the connection state are published downstream as [BiliMessage]s.
 ///
 /// *`config`: Configuration of the connection
-/// * `url`: Socket Server's url
 ///
-async fn start_worker(config: ClientConfig, url: &'static str) {
+async fn start_worker(config: ClientConfig) {
+  let ClientConfig {
+    room_id,
+    user_id,
+    protover,
+    api_base,
+    transport,
+    tls_connector,
+    backoff,
+    heartbeat,
+    record_dir,
+    raw_passthrough,
+    dedup_window,
+    forward_enters,
+    status,
+    downstream,
+  } = config;
+
+  let mut recorder = record_dir.and_then(|dir| create_recorder(&dir, room_id));
+  let mut pipeline = Pipeline::new(
+    room_id,
+    status.clone(),
+    downstream.clone(),
+    raw_passthrough,
+    dedup_window,
+    forward_enters,
+  );
+
+  // empty until fetched in the first iteration
+  let mut endpoints = Endpoints::default();
+  // consecutive failures since the last time we entered the room
+  let mut failures = 0;
   loop {
-    let ClientConfig {
-      room_id,
-      user_id,
-      shutdown,
-      downstream,
-    } = config.clone();
-    let (cli, _) = connect_async(url).await.unwrap();
-    let (mut write, read) = cli.split();
+    if status.shutdown.load(Ordering::Relaxed) {
+      break;
+    }
 
-    // this task handles the sending message stream to the Bilibili's live server
-    let heartbeat_task = {
-      let shutdown = shutdown.clone();
-      tokio::spawn(async move {
-        let mut heartbeat_stream = create_heartbeat_stream(room_id, user_id, shutdown).await;
-        write.send_all(&mut heartbeat_stream).await.unwrap();
-        // our sending stream has ended, send a close frame just for courtesy
-        write.send(Message::Close(None)).await.unwrap();
-      })
+    let Some(url) = endpoints.next_url() else {
+      // all servers are tried, refresh the list
+      endpoints = Endpoints::fetch(&api_base, room_id, transport).await;
+      continue;
     };
 
-    // this task handles reading message from Bilibili's live server and
-    // send the message to downstream
-    read
-      .for_each(|msg| async {
-        let msg = match msg {
-          Ok(msg) => msg,
-          Err(err) => {
-            error!("{}", err);
-            return;
-          }
-        };
-        match msg {
-          Message::Binary(buf) => {
-            let msg = BiliWebsocketMessage::from_binary(buf).unwrap();
-            for inner in msg.parse() {
-              let bili_msg = BiliMessage::from_raw_wesocket_message(inner);
-              if let Some(msg) = bili_msg {
-                if let Err(err) = downstream.send(msg) {
-                  error!("{}", err);
-                }
-              }
-            }
-          }
-          msg => {
-            // we don't expect Bilibili to send other types of message
-            // trace for debugging use
-            trace!("Room {} Received Message: {}", room_id, msg);
-          }
+    publish(
+      &downstream,
+      BiliMessage::Connecting {
+        room_id,
+        attempt: failures + 1,
+      },
+    );
+    let connector = tls_connector.clone().map(Connector::NativeTls);
+    let reason = match connect_async_tls_with_config(&url, None, false, connector).await {
+      Ok((cli, _)) => {
+        info!("Room {} Connected to {}", room_id, url);
+        publish(
+          &downstream,
+          BiliMessage::Connected {
+            room_id,
+            url: url.clone(),
+          },
+        );
+        let entry = BiliWebsocketMessage::entry(room_id, user_id, protover, &endpoints.token);
+        let (entered, reason) =
+          run_connection(cli, entry, &heartbeat, &mut recorder, &mut pipeline).await;
+        pipeline.flush();
+        status.reset_latency();
+
+        // terminated, exit
+        if status.shutdown.load(Ordering::Relaxed) {
+          break;
         }
-      })
-      .await;
 
-    // In normal execution the read task runs forever. If it is terminated either server stopped the connection
-    // or we have terminated. Either case abort the write task accordingly.
-    heartbeat_task.abort();
+        if entered {
+          failures = 0;
+        }
+        // the token might have expired, refresh the server list
+        endpoints = Endpoints::default();
+        warn!("Room {} Connection Lost: {}", room_id, reason);
+        reason
+      }
+      Err(err) => {
+        warn!("Room {} Fail Connecting to {}: {}", room_id, url, err);
+        err.to_string()
+      }
+    };
+    publish(&downstream, BiliMessage::Disconnected { room_id, reason });
 
-    // terminated, exit
-    if shutdown.load(Ordering::Relaxed) {
-      break;
+    failures += 1;
+    if backoff.gives_up(failures) {
+      error!(
+        "Room {} Gave Up Reconnecting after {} Attempts",
+        room_id, failures
+      );
+      status.stopped.store(true, Ordering::Relaxed);
+      publish(
+        &downstream,
+        BiliMessage::GaveUp {
+          room_id,
+          attempts: failures,
+        },
+      );
+      finish_recording(recorder, room_id).await;
+      return;
     }
+    let delay = backoff.delay(failures - 1);
+    info!("Room {} Reconnecting in {:?}...", room_id, delay);
+    sleep_unless_shutdown(delay, &status.shutdown).await;
+  }
+
+  finish_recording(recorder, room_id).await;
+  info!("Websocket Connection to Room {} Terminated", room_id);
+}
+
+/// Runs an established connection until it is closed, forwarding the
+/// messages received downstream. The connection is closed when the server
+/// goes silent for longer than the heartbeat timeout. Returns whether the
+/// server has acknowledged our entry, and the reason the connection ends.
+async fn run_connection(
+  cli: WebSocketStream<MaybeTlsStream<TcpStream>>,
+  entry: BiliWebsocketMessage,
+  heartbeat: &Heartbeat,
+  recorder: &mut Option<Recorder>,
+  pipeline: &mut Pipeline,
+) -> (bool, String) {
+  let room_id = pipeline.room_id();
+  let (mut write, mut read) = cli.split();
+
+  // this task handles the sending message stream to the Bilibili's live server
+  let heartbeat_task = {
+    let status = pipeline.status().clone();
+    let interval = heartbeat.interval;
+    tokio::spawn(async move {
+      let heartbeat_stream = create_heartbeat_stream(entry, interval, status).await;
+      if let Err(err) = write.send_all(&mut heartbeat_stream.map(Ok)).await {
+        warn!("Room {} Fail Sending Heartbeat: {}", room_id, err);
+        return;
+      }
+      // our sending stream has ended, send a close frame just for courtesy
+      let _ = write.send(Message::Close(None)).await;
+    })
+  };
+
+  // read messages from Bilibili's live server and send them to downstream
+  let mut entered = false;
+  // last time the server proved to be alive
+  let mut last_reply = Instant::now();
+  let reason = loop {
+    // messages held by the pipeline are forwarded on time in quiet rooms
+    let held = pipeline.next_deadline();
+    let msg = tokio::select! {
+      msg = tokio::time::timeout_at(last_reply + heartbeat.timeout, read.next()) => match msg {
+        Ok(Some(Ok(msg))) => msg,
+        Ok(Some(Err(err))) => break err.to_string(),
+        Ok(None) => break "Connection Closed".to_string(),
+        Err(_) => break format!("No Reply from Server in {:?}", heartbeat.timeout),
+      },
+      _ = tokio::time::sleep_until(held.unwrap_or_else(Instant::now)), if held.is_some() => {
+        pipeline.forward_expired();
+        continue;
+      }
+    };
+    match msg {
+      Message::Binary(buf) => {
+        record(recorder, room_id, &buf);
+        let received = pipeline.forward_frame(buf);
+        if received.alive {
+          last_reply = Instant::now();
+        }
+        if received.entry_reply && !entered {
+          entered = true;
+          pipeline.publish(BiliMessage::EntryAcknowledged { room_id });
+        }
+      }
+      Message::Close(frame) => {
+        break match frame {
+          Some(frame) => format!("Closed by Server: {frame}"),
+          None => "Closed by Server".to_string(),
+        }
+      }
+      msg => {
+        // we don't expect Bilibili to send other types of message
+        // trace for debugging use
+        trace!("Room {} Received Message: {}", room_id, msg);
+      }
+    }
+  };
 
-    warn!("Connection Lost, Reconnecting...");
+  // In normal execution the read loop runs forever. If it is terminated either server stopped the connection
+  // or we have terminated. Either case abort the write task accordingly.
+  heartbeat_task.abort();
+  (entered, reason)
+}
+
+// create the recording file of a session in the directory
+fn create_recorder(dir: &Path, room_id: i64) -> Option<Recorder> {
+  let started = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_millis();
+  let path = dir.join(format!("room-{room_id}-{started}.rec"));
+  match Recorder::create(&path) {
+    Ok(recorder) => {
+      info!("Room {} Recording to {}", room_id, path.display());
+      Some(recorder)
+    }
+    Err(err) => {
+      warn!("Room {} Fail Creating Recording: {}", room_id, err);
+      None
+    }
   }
+}
 
-  // sanity check
-  assert!(config.shutdown.load(Ordering::Relaxed));
-  info!("Websocket Connection to Room {} Terminated", config.room_id);
+// record a frame, recording stops at the first error
+fn record(recorder: &mut Option<Recorder>, room_id: i64, frame: &[u8]) {
+  if let Some(err) = recorder.as_ref().and_then(|r| r.record(frame).err()) {
+    warn!(
+      "Room {} Fail Recording, Recording Stopped: {}",
+      room_id, err
+    );
+    *recorder = None;
+  }
+}
+
+// wait for the recorded frames to be written out
+async fn finish_recording(recorder: Option<Recorder>, room_id: i64) {
+  let Some(recorder) = recorder else {
+    return;
+  };
+  match tokio::task::spawn_blocking(move || recorder.finish()).await {
+    Ok(Ok(())) => {}
+    Ok(Err(err)) => warn!("Room {} Fail Finishing Recording: {}", room_id, err),
+    Err(err) => warn!("Room {} Fail Finishing Recording: {}", room_id, err),
+  }
+}
+
+/// Replays recorded frames in place of a live connection, delivering
+/// each frame after its scaled offset. Connection-state events mark the
+/// start and the end of the replay.
+async fn start_replay(
+  frames: Vec<RecordedFrame>,
+  speed: ReplaySpeed,
+  source: String,
+  mut pipeline: Pipeline,
+) {
+  let room_id = pipeline.room_id();
+  let status = pipeline.status().clone();
+  pipeline.publish(BiliMessage::Connected {
+    room_id,
+    url: source,
+  });
+  let started = Instant::now();
+  let mut entered = false;
+  for RecordedFrame { offset, frame } in frames {
+    // a slowed down replay may schedule frames beyond what an Instant holds
+    let Some(due) = started.checked_add(speed.scale(offset)) else {
+      warn!(
+        "Replay of Room {} Stopped, Frames Are Due Too Late",
+        room_id
+      );
+      break;
+    };
+    sleep_unless_shutdown(
+      due.saturating_duration_since(Instant::now()),
+      &status.shutdown,
+    )
+    .await;
+    if status.shutdown.load(Ordering::Relaxed) {
+      info!("Replay of Room {} Terminated", room_id);
+      return;
+    }
+    let received = pipeline.forward_frame(frame);
+    if received.entry_reply && !entered {
+      entered = true;
+      pipeline.publish(BiliMessage::EntryAcknowledged { room_id });
+    }
+  }
+  pipeline.flush();
+  status.stopped.store(true, Ordering::Relaxed);
+  pipeline.publish(BiliMessage::Disconnected {
+    room_id,
+    reason: "Replay Finished".to_string(),
+  });
+}
+
+/// sleeps for the given duration, waking up early if shut down
+async fn sleep_unless_shutdown(duration: Duration, shutdown: &AtomicBool) {
+  let deadline = Instant::now() + duration;
+  while !shutdown.load(Ordering::Relaxed) {
+    let now = Instant::now();
+    if now >= deadline {
+      break;
+    }
+    tokio::time::sleep((deadline - now).min(SHUTDOWN_POLL_INTERVAL)).await;
+  }
 }
 
 /// Creates a message stream to Bilibili's server with the following structure:
 /// [Entry Security Message]
-/// | every 20s
+/// | every interval (20s by default)
 /// V
 /// Heartbeat
 /// ...
 async fn create_heartbeat_stream(
-  room_id: i64,
-  uid: Option<u64>,
-  shutdown: Arc<AtomicBool>,
-) -> impl Stream<Item = std::result::Result<Message, Error>> {
+  entry: BiliWebsocketMessage,
+  interval: Duration,
+  status: Arc<RoomStatus>,
+) -> impl Stream<Item = Message> {
   let (mut tx, rx) = futures_channel::mpsc::unbounded();
   tokio::spawn(async move {
-    let entry = BiliWebsocketMessage::entry(room_id, uid);
-    tx.send(entry).await.unwrap();
+    if tx.send(entry).await.is_err() {
+      return;
+    }
     loop {
-      if shutdown.load(Ordering::Relaxed) {
+      if status.shutdown.load(Ordering::Relaxed) {
         break;
       }
-      tokio::time::sleep(Duration::from_secs(20)).await;
-      tx.send(BiliWebsocketMessage::heartbeat()).await.unwrap();
+      tokio::time::sleep(interval).await;
+      // receiver is dropped when the connection ends
+      if tx.send(BiliWebsocketMessage::heartbeat()).await.is_err() {
+        break;
+      }
+      status.heartbeat_sent();
     }
   });
-  rx.map(|msg| Ok(Message::Binary(msg.to_vec())))
+  rx.map(|msg| Message::Binary(msg.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use tokio::net::TcpListener;
+  use tokio_tungstenite::accept_async;
+
+  use byteorder::{BigEndian, WriteBytesExt};
+
+  use crate::client::mock::{
+    entry_reply, host, notification, self_signed, stub_api, MockServer, Script, LIVE_SESSION,
+  };
+  use crate::client::recording::FLUSH_INTERVAL;
+  use crate::util::temp_dir;
+
+  // a websocket server that accepts connections and never replies
+  async fn silent_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+      while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+          if let Ok(mut ws) = accept_async(stream).await {
+            while ws.next().await.is_some() {}
+          }
+        });
+      }
+    });
+    port
+  }
+
+  // a port nobody listens on
+  fn dead_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+  }
+
+  // start a client connecting to room 42 as user 7
+  fn start_client(
+    api_base: String,
+    tls_connector: Option<native_tls::TlsConnector>,
+    transport: Transport,
+  ) -> BiliClient {
+    start_client_with_options(
+      ClientOptions {
+        api_base,
+        tls_connector,
+        ..Default::default()
+      },
+      transport,
+    )
+    .0
+  }
+
+  fn start_client_with_options(
+    options: ClientOptions,
+    transport: Transport,
+  ) -> (BiliClient, tokio::sync::broadcast::Receiver<BiliMessage>) {
+    let (tx, rx) = tokio::sync::broadcast::channel(100);
+    let mut cli = BiliClient::with_options(tx, options);
+    cli.start(42, Some(7), transport).unwrap();
+    (cli, rx)
+  }
+
+  // receive messages until one matches the predicate
+  async fn recv_until(
+    rx: &mut tokio::sync::broadcast::Receiver<BiliMessage>,
+    mut pred: impl FnMut(&BiliMessage) -> bool,
+  ) -> Vec<BiliMessage> {
+    let mut received = vec![];
+    tokio::time::timeout(Duration::from_secs(10), async {
+      loop {
+        let msg = rx.recv().await.unwrap();
+        let done = pred(&msg);
+        received.push(msg);
+        if done {
+          break;
+        }
+      }
+    })
+    .await
+    .unwrap();
+    received
+  }
+
+  #[tokio::test]
+  async fn test_fetch_ws_config() {
+    let api_base = stub_api("token", vec![host(1, 4), host(2, 5), host(3, 6)]).await;
+    let config = WsConfig::fetch(&api_base, 1).await.unwrap();
+    assert_eq!("token", config.token);
+
+    // every host appears once, in rotating order
+    let urls = config.ws_urls();
+    let start = urls
+      .iter()
+      .position(|url| url == "ws://localhost:1/sub")
+      .unwrap();
+    let rotated: Vec<&String> = urls.iter().cycle().skip(start).take(3).collect();
+    assert_eq!(
+      vec![
+        "ws://localhost:1/sub",
+        "ws://localhost:2/sub",
+        "ws://localhost:3/sub"
+      ],
+      rotated
+    );
+
+    let mut tls_urls = config.wss_urls();
+    tls_urls.sort();
+    assert_eq!(
+      vec![
+        "wss://localhost:4/sub",
+        "wss://localhost:5/sub",
+        "wss://localhost:6/sub"
+      ],
+      tls_urls
+    );
+  }
+
+  #[tokio::test]
+  async fn test_connect_with_fetched_token() {
+    let mut server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    // dead servers are rotated past
+    let api_base = stub_api(
+      "secret",
+      vec![host(dead_port(), 0), host(port, 0), host(dead_port(), 0)],
+    )
+    .await;
+
+    let _cli = start_client(api_base, None, Transport::Ws);
+
+    let entry = server.recv_entry().await;
+    assert_eq!("secret", entry["key"]);
+    assert_eq!(42, entry["roomid"]);
+    assert_eq!(7, entry["uid"]);
+  }
+
+  #[tokio::test]
+  async fn test_connect_over_tls() {
+    let (acceptor, connector) = self_signed();
+    let mut tls_server = MockServer::start_tls(Script::default(), acceptor).await;
+    let tls_port = tls_server.port();
+    let mut server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    let api_base = stub_api("secret", vec![host(port, tls_port)]).await;
+
+    let _cli = start_client(api_base, Some(connector), Transport::Wss);
+
+    let entry = tls_server.recv_entry().await;
+    assert_eq!("secret", entry["key"]);
+    // plain server is not used
+    assert!(server.no_entry_within(Duration::from_millis(500)).await);
+  }
+
+  #[tokio::test]
+  async fn test_tls_falls_back_to_plain() {
+    let (_, connector) = self_signed();
+    let mut server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    let api_base = stub_api("secret", vec![host(port, dead_port())]).await;
+
+    let _cli = start_client(api_base, Some(connector), Transport::Wss);
+
+    let entry = server.recv_entry().await;
+    assert_eq!("secret", entry["key"]);
+  }
+
+  #[tokio::test]
+  async fn test_tls_rejects_untrusted_certificate() {
+    // the client doesn't trust the server's certificate, so it falls back to plain ws
+    let (acceptor, _) = self_signed();
+    let mut tls_server = MockServer::start_tls(Script::default(), acceptor).await;
+    let tls_port = tls_server.port();
+    let mut server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    let api_base = stub_api("secret", vec![host(port, tls_port)]).await;
+
+    let _cli = start_client(api_base, None, Transport::Wss);
+
+    server.recv_entry().await;
+    assert!(tls_server.no_entry_within(Duration::from_millis(500)).await);
+  }
+
+  #[test]
+  fn test_backoff_delay() {
+    let backoff = Backoff {
+      initial: Duration::from_secs(1),
+      max: Duration::from_secs(10),
+      max_retries: None,
+    };
+    for _ in 0..100 {
+      let delay = backoff.delay(0);
+      assert!(Duration::from_millis(500) <= delay && delay <= Duration::from_secs(1));
+      let delay = backoff.delay(2);
+      assert!(Duration::from_secs(2) <= delay && delay <= Duration::from_secs(4));
+      // capped
+      let delay = backoff.delay(10);
+      assert!(Duration::from_secs(5) <= delay && delay <= Duration::from_secs(10));
+      let delay = backoff.delay(u32::MAX);
+      assert!(Duration::from_secs(5) <= delay && delay <= Duration::from_secs(10));
+    }
+  }
+
+  #[test]
+  fn test_backoff_gives_up() {
+    let mut backoff = Backoff::default();
+    assert!(!backoff.gives_up(u32::MAX));
+    backoff.max_retries = Some(2);
+    assert!(!backoff.gives_up(2));
+    assert!(backoff.gives_up(3));
+  }
+
+  #[test]
+  fn test_raw_passthrough_from_str() {
+    assert_eq!(Ok(RawPassthrough::Off), "".parse());
+    assert_eq!(Ok(RawPassthrough::All), "*".parse());
+    let cmds: RawPassthrough = "HOT_RANK_CHANGED, STOP_LIVE_ROOM_LIST".parse().unwrap();
+    assert!(cmds.forwards("HOT_RANK_CHANGED"));
+    assert!(cmds.forwards("STOP_LIVE_ROOM_LIST"));
+    assert!(!cmds.forwards("WIDGET_BANNER"));
+    assert!(!RawPassthrough::Off.forwards("WIDGET_BANNER"));
+    assert!(RawPassthrough::All.forwards("WIDGET_BANNER"));
+  }
+
+  #[tokio::test]
+  async fn test_connection_events() {
+    let server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    let api_base = stub_api("secret", vec![host(port, 0)]).await;
+
+    let (_cli, mut rx) = start_client_with_options(
+      ClientOptions {
+        api_base,
+        ..Default::default()
+      },
+      Transport::Ws,
+    );
+
+    let received = recv_until(&mut rx, |msg| {
+      matches!(msg, BiliMessage::EntryAcknowledged { .. })
+    })
+    .await;
+    assert!(matches!(
+      received.as_slice(),
+      [
+        BiliMessage::Connecting {
+          room_id: 42,
+          attempt: 1
+        },
+        BiliMessage::Connected { room_id: 42, .. },
+        BiliMessage::EntryAcknowledged { room_id: 42 },
+      ]
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_gives_up_after_max_retries() {
+    let api_base = stub_api("secret", vec![host(dead_port(), 0)]).await;
+
+    let (mut cli, mut rx) = start_client_with_options(
+      ClientOptions {
+        api_base,
+        backoff: Backoff {
+          initial: Duration::from_millis(1),
+          max: Duration::from_millis(10),
+          max_retries: Some(2),
+        },
+        ..Default::default()
+      },
+      Transport::Ws,
+    );
+
+    let received = recv_until(&mut rx, |msg| matches!(msg, BiliMessage::GaveUp { .. })).await;
+    let attempts: Vec<u32> = received
+      .iter()
+      .filter_map(|msg| match msg {
+        BiliMessage::Connecting { attempt, .. } => Some(*attempt),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(vec![1, 2, 3], attempts);
+    assert!(matches!(
+      received.last(),
+      Some(BiliMessage::GaveUp {
+        room_id: 42,
+        attempts: 3
+      })
+    ));
+    assert!(cli.is_stopped(42));
+
+    // a failed room can be started again
+    cli.start(42, Some(7), Transport::Ws).unwrap();
+    recv_until(&mut rx, |msg| matches!(msg, BiliMessage::Connecting { .. })).await;
+  }
+
+  #[tokio::test]
+  async fn test_heartbeat_latency() {
+    let server = MockServer::start(Script::default()).await;
+    let port = server.port();
+    let api_base = stub_api("secret", vec![host(port, 0)]).await;
+
+    let (cli, mut rx) = start_client_with_options(
+      ClientOptions {
+        api_base,
+        heartbeat: Heartbeat {
+          interval: Duration::from_millis(50),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      Transport::Ws,
+    );
+
+    assert_eq!(None, cli.latency(42));
+    recv_until(&mut rx, |msg| matches!(msg, BiliMessage::RoomPopularity(1))).await;
+    let latency = cli.latency(42).unwrap();
+    assert!(latency < Duration::from_secs(10));
+    assert_eq!(None, cli.latency(1));
+  }
+
+  #[tokio::test]
+  async fn test_reconnect_silent_server() {
+    let port = silent_server().await;
+    let api_base = stub_api("secret", vec![host(port, 0)]).await;
+
+    let (_cli, mut rx) = start_client_with_options(
+      ClientOptions {
+        api_base,
+        backoff: Backoff {
+          initial: Duration::from_millis(1),
+          max: Duration::from_millis(10),
+          max_retries: None,
+        },
+        heartbeat: Heartbeat {
+          interval: Duration::from_millis(50),
+          timeout: Duration::from_millis(200),
+        },
+        ..Default::default()
+      },
+      Transport::Ws,
+    );
+
+    let received = recv_until(&mut rx, |msg| {
+      matches!(msg, BiliMessage::Connecting { attempt: 2, .. })
+    })
+    .await;
+    assert!(received.iter().any(|msg| matches!(
+      msg,
+      BiliMessage::Disconnected { reason, .. } if reason.starts_with("No Reply")
+    )));
+  }
+
+  // danmu contents & gift names among the messages
+  fn events(received: &[BiliMessage]) -> Vec<String> {
+    received
+      .iter()
+      .filter_map(|msg| match msg {
+        BiliMessage::Danmu(danmu) => Some(danmu.content().clone()),
+        BiliMessage::Gift(gift) => Some(gift.gift_name().clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn replay_client(
+    path: &Path,
+    speed: ReplaySpeed,
+  ) -> (BiliClient, tokio::sync::broadcast::Receiver<BiliMessage>) {
+    let (tx, rx) = tokio::sync::broadcast::channel(100);
+    let mut cli = BiliClient::new(tx);
+    cli.replay(42, path, speed).unwrap();
+    (cli, rx)
+  }
+
+  fn replay_finished(msg: &BiliMessage) -> bool {
+    matches!(msg, BiliMessage::Disconnected { reason, .. } if reason == "Replay Finished")
+  }
+
+  #[tokio::test]
+  async fn test_record_and_replay() {
+    let dir = temp_dir("record-and-replay");
+    let server = MockServer::start(Script {
+      batch_size: 2,
+      ..Script::from_recording(LIVE_SESSION).unwrap()
+    })
+    .await;
+    let api_base = stub_api("secret", vec![host(server.port(), 0)]).await;
+
+    let (cli, mut rx) = start_client_with_options(
+      ClientOptions {
+        api_base,
+        record_dir: Some(dir.clone()),
+        ..Default::default()
+      },
+      Transport::Ws,
+    );
+    let mut live = vec![];
+    while events(&live).len() < 4 {
+      live.extend(recv_until(&mut rx, |_| true).await);
+    }
+    drop(cli);
+    // the recorder's thread writes the frames out within a flush interval
+    tokio::time::sleep(FLUSH_INTERVAL * 2).await;
+
+    let recordings: Vec<PathBuf> = std::fs::read_dir(&dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .collect();
+    assert_eq!(1, recordings.len());
+
+    let (_cli, mut rx) = replay_client(&recordings[0], ReplaySpeed::Instant);
+    let replayed = recv_until(&mut rx, replay_finished).await;
+    assert_eq!(events(&live), events(&replayed));
+    assert!(matches!(
+      replayed.as_slice(),
+      [
+        BiliMessage::Connected { room_id: 42, .. },
+        BiliMessage::EntryAcknowledged { room_id: 42 },
+        ..
+      ]
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_replay_speed() {
+    let dir = temp_dir("replay-speed");
+    let path = dir.join("session.rec");
+    // two notifications recorded 300ms apart
+    let mut recording = vec![];
+    let bodies = Script::from_recording(LIVE_SESSION).unwrap().notifications;
+    for (offset, frame) in [
+      (0, entry_reply()),
+      (0, notification(&bodies[0])),
+      (300, notification(&bodies[1])),
+    ] {
+      let frame = frame.to_vec();
+      recording.write_u64::<BigEndian>(offset).unwrap();
+      recording
+        .write_u32::<BigEndian>(frame.len() as u32)
+        .unwrap();
+      recording.extend(frame);
+    }
+    std::fs::write(&path, recording).unwrap();
+
+    for (speed, min, max) in [
+      (ReplaySpeed::Realtime, 300, u64::MAX),
+      (ReplaySpeed::Accelerated(3.0), 100, u64::MAX),
+      (ReplaySpeed::Instant, 0, 100),
+    ] {
+      let started = Instant::now();
+      let (_cli, mut rx) = replay_client(&path, speed);
+      let replayed = recv_until(&mut rx, replay_finished).await;
+      let elapsed = started.elapsed();
+      assert_eq!(vec!["晚上好", "小花花"], events(&replayed));
+      assert!(
+        Duration::from_millis(min) <= elapsed,
+        "{speed:?} {elapsed:?}"
+      );
+      assert!(
+        elapsed <= Duration::from_millis(max),
+        "{speed:?} {elapsed:?}"
+      );
+    }
+
+    // slowed down beyond what can be scheduled, the replay stops early
+    let (_cli, mut rx) = replay_client(&path, ReplaySpeed::Accelerated(1e-300));
+    let replayed = recv_until(&mut rx, replay_finished).await;
+    assert_eq!(vec!["晚上好"], events(&replayed));
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_replay_missing_recording() {
+    let (tx, _rx) = tokio::sync::broadcast::channel(100);
+    let mut cli = BiliClient::new(tx);
+    assert!(cli
+      .replay(42, "no-such-recording.rec", ReplaySpeed::Instant)
+      .is_err());
+  }
 }
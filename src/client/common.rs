@@ -7,7 +7,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use super::{BiliWebsocketInner, NotificationBody};
 use derive_getters::Getters;
 use serde::{Deserialize, Serialize};
-use serde_json::{Number, Value};
+use serde_json::Value;
 use ts_rs::TS;
 
 /// The type representing a Bilibili's message received
@@ -21,8 +21,106 @@ pub enum BiliMessage {
   Danmu(DanmuMessage),
   /// Someone sent gifts
   Gift(GiftMessage),
+  /// Someone sent a Super Chat (醒目留言)
+  SuperChat(SuperChatMessage),
+  /// Super Chats removed from the room, e.g. by moderators
+  SuperChatDelete {
+    #[ts(type = "Array<number>")]
+    ids: Vec<u64>,
+  },
+  /// Someone entered, followed or shared the room
+  Interaction(InteractionMessage),
+  /// A guard member entered the room with an entrance effect
+  GuardEntry(GuardEntryMessage),
+  /// Someone bought or renewed a guard (大航海)
+  GuardPurchase(GuardPurchaseMessage),
+  /// A red pocket (红包抽奖) is sent, viewers join by sending its danmu
+  RedPocketStart(RedPocketMessage),
+  /// Winners of a red pocket are drawn
+  RedPocketWinners {
+    #[ts(type = "number")]
+    lot_id: u64,
+    winners: Vec<LotteryWinner>,
+  },
+  /// The streamer starts a lottery (天选时刻), viewers join by sending its danmu
+  AnchorLotteryStart(AnchorLotteryMessage),
+  /// A lottery stops taking participants
+  AnchorLotteryEnd {
+    #[ts(type = "number")]
+    id: u64,
+  },
+  /// Winners of a lottery are drawn
+  AnchorLotteryAward {
+    #[ts(type = "number")]
+    id: u64,
+    award_name: String,
+    winners: Vec<LotteryWinner>,
+  },
+  /// Progress of a PK battle (PK对战) the room is in
+  Pk(PkEvent),
+  /// The room went live or offline, changed its title,
+  /// or was warned or cut off by moderators
+  RoomEvent(RoomEvent),
   // Auto Room Popularity Update
   RoomPopularity(i32),
+  /// Number of viewers on the online rank (高能榜)
+  OnlineRankCount {
+    #[ts(type = "number")]
+    count: u64,
+  },
+  /// Top viewers of the online rank, at most 3
+  OnlineRankTop(Vec<OnlineRankUser>),
+  /// Number of viewers who have watched the live (xx人看过)
+  WatchedChange {
+    #[ts(type = "number")]
+    num: u64,
+    // the number as displayed, e.g. "1.2万人看过"
+    text: String,
+  },
+  /// Total number of likes of the live
+  LikeCount {
+    #[ts(type = "number")]
+    count: u64,
+  },
+  /// A notification whose command has no typed variant yet, forwarded only
+  /// if enabled with [ClientOptions::raw_passthrough](super::ClientOptions)
+  Raw {
+    cmd: String,
+    // the whole notification
+    #[ts(type = "any")]
+    body: Value,
+  },
+  /// Client starts connecting to the room's websocket server,
+  /// `attempt` counts from 1 since the last time we entered the room
+  Connecting {
+    #[ts(type = "number")]
+    room_id: i64,
+    attempt: u32,
+  },
+  /// Websocket connection is established
+  Connected {
+    #[ts(type = "number")]
+    room_id: i64,
+    url: String,
+  },
+  /// Server accepted our entry, messages of the room start coming
+  EntryAcknowledged {
+    #[ts(type = "number")]
+    room_id: i64,
+  },
+  /// Connection is lost or fails to be established
+  Disconnected {
+    #[ts(type = "number")]
+    room_id: i64,
+    reason: String,
+  },
+  /// Client stopped reconnecting after too many consecutive failures,
+  /// the room is marked as failed
+  GaveUp {
+    #[ts(type = "number")]
+    room_id: i64,
+    attempts: u32,
+  },
 }
 
 /// The type representing a bullet screen message
@@ -61,6 +159,18 @@ pub struct DanmuMessage {
 
   // 舰队身份
   guard: GuardType,
+
+  // 弹幕模式: 1 -> 滚动, 4 -> 底部, 5 -> 顶部
+  #[ts(type = "number")]
+  mode: u64,
+  // 弹幕颜色, e.g. "#FFFFFF"
+  color: String,
+  // 弹幕id, 旧的消息没有
+  id_str: Option<String>,
+  // 表情弹幕的表情
+  emoticon: Option<Emoticon>,
+  // 回复的用户名
+  reply_uname: Option<String>,
 }
 
 impl DanmuMessage {
@@ -89,76 +199,98 @@ impl DanmuMessage {
   fn from_raw(value: &NotificationBody) -> Option<DanmuMessage> {
     let info = value.get("info")?;
     let info = info.as_array()?;
-    let danmu_info = info.get(0)?.as_array()?;
+    let danmu_info = info.first()?.as_array()?;
+    // info中的数字字段, 缺失时为0
+    let number =
+      |array: &[Value], index: usize| array.get(index).and_then(Value::as_u64).unwrap_or(0);
 
     let is_gift_auto = danmu_info.get(9)?.as_u64().unwrap_or(0);
     let is_gift_auto = is_gift_auto == 2;
-    let sent_time = danmu_info
-      .get(4)
-      .unwrap_or(&Value::Number(Number::from_f64(0.0).unwrap()))
-      .as_u64()
-      .unwrap_or(0);
+    let sent_time = number(danmu_info, 4);
+    // 弹幕模式 & 颜色
+    let mode = number(danmu_info, 1);
+    let color = rgb(number(danmu_info, 3));
+    // 表情弹幕, 普通弹幕为"{}"
+    let emoticon = danmu_info.get(13).and_then(Emoticon::from_raw);
+    // 额外信息是一个json字符串
+    let extra: Value = danmu_info
+      .get(15)
+      .and_then(|extra| extra.get("extra"))
+      .and_then(Value::as_str)
+      .and_then(|extra| serde_json::from_str(extra).ok())
+      .unwrap_or_default();
+    let non_empty = |field: &str| {
+      extra
+        .get(field)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+    };
+    let id_str = non_empty("id_str");
+    let reply_uname = non_empty("reply_uname");
 
     // 用array传是哪个天才想出来的？
-    let sender_info = info[2].as_array()?;
+    let sender_info = info.get(2)?.as_array()?;
     // uid
-    let uid = sender_info[0].as_u64().unwrap_or(0);
+    let uid = number(sender_info, 0);
     // 用户名
-    let uname = sender_info[1].as_str().unwrap_or("B站用户");
+    let uname = sender_info
+      .get(1)
+      .and_then(Value::as_str)
+      .unwrap_or("B站用户");
     let uname = uname.to_string();
     // 房管: 0 -> 非, 1 -> 是
-    let is_manager = sender_info[2].as_u64().unwrap_or(0);
-    let is_manager = is_manager == 1;
+    let is_manager = number(sender_info, 2) == 1;
     // vip: 0 -> 非, 1 -> 是
-    let is_vip = sender_info[3].as_u64().unwrap_or(0);
-    let is_vip = is_vip == 1;
+    let is_vip = number(sender_info, 3) == 1;
     // 年费vip: 0 -> 非, 1 -> 是
-    let is_svip = sender_info[4].as_u64().unwrap_or(0);
-    let is_svip = is_svip == 1;
+    let is_svip = number(sender_info, 4) == 1;
     // 正式会员: 5000->非 10000->是
-    let is_full_member = sender_info[5].as_u64().unwrap_or(5000);
-    let is_full_member = is_full_member == 10000;
+    let is_full_member = number(sender_info, 5) == 10000;
 
     // 弹幕内容
-    let content = info[1].as_str().unwrap_or("");
+    let content = info.get(1).and_then(Value::as_str).unwrap_or("");
     let content = content.to_string();
 
-    // 勋章
-    let medal_info = info[3].as_array();
-    let medal = if let Some(medal) = medal_info {
-      // 勋章可能是[]
-      if medal.len() >= 4 {
-        let level = medal[0].as_u64().unwrap_or(0);
-        let name = medal[1].as_str().unwrap_or("").to_string();
-        let streamer_name = medal[2].as_str().unwrap_or("").to_string();
-        let streamer_roomid = medal[3].as_u64().unwrap_or(0);
-        Some(Medal {
-          level,
-          name,
-          streamer_name,
-          streamer_roomid,
-        })
-      } else {
-        None
-      }
-    } else {
-      None
-    };
+    // 勋章, 可能是[]
+    let medal = info
+      .get(3)
+      .and_then(Value::as_array)
+      .filter(|medal| medal.len() >= 4)
+      .map(|medal| {
+        let string = |index: usize| {
+          medal
+            .get(index)
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string()
+        };
+        Medal {
+          level: number(medal, 0),
+          name: string(1),
+          streamer_name: string(2),
+          streamer_roomid: number(medal, 3),
+          color: rgb(number(medal, 4)),
+          color_border: rgb(number(medal, 7)),
+          color_start: rgb(number(medal, 8)),
+          color_end: rgb(number(medal, 9)),
+          guard: number(medal, 10).into(),
+          is_lighted: number(medal, 11) == 1,
+        }
+      });
 
     // 用户等级
-    let ul_info = info[4].as_array();
+    let ul_info = info.get(4).and_then(Value::as_array);
     let (ul, ul_rank) = if let Some(ul_info) = ul_info {
-      let ul = ul_info[0].as_u64().unwrap_or(0);
-      let ul_rank = ul_info[1].as_str().unwrap_or("");
+      let ul = number(ul_info, 0);
+      let ul_rank = ul_info.get(1).and_then(Value::as_str).unwrap_or("");
       let ul_rank = ul_rank.to_string();
       (ul, ul_rank)
     } else {
       (0, ">50000".to_string())
     };
 
-    let guard_info = info[7].as_u64().unwrap_or(0);
-    // println!("{}", guard_info);
-    let guard: GuardType = guard_info.into();
+    let guard: GuardType = number(info, 7).into();
 
     Some(DanmuMessage {
       uid,
@@ -174,10 +306,50 @@ impl DanmuMessage {
       ul,
       ul_rank,
       guard,
+      mode,
+      color,
+      id_str,
+      emoticon,
+      reply_uname,
     })
   }
 }
 
+/// A sticker sent as a bullet screen comment (表情弹幕)
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/Emoticon.ts")]
+pub struct Emoticon {
+  // e.g. "upower_[主播_比心]"
+  unique: String,
+  url: String,
+  // size of the image in pixels
+  #[ts(type = "number")]
+  width: u64,
+  #[ts(type = "number")]
+  height: u64,
+}
+
+impl Emoticon {
+  fn from_raw(value: &Value) -> Option<Emoticon> {
+    Some(Emoticon {
+      unique: value
+        .get("emoticon_unique")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string(),
+      url: value.get("url")?.as_str()?.to_string(),
+      width: value.get("width").and_then(Value::as_u64).unwrap_or(0),
+      height: value.get("height").and_then(Value::as_u64).unwrap_or(0),
+    })
+  }
+}
+
+// colors are sent as integers
+fn rgb(color: u64) -> String {
+  format!("#{:06X}", color)
+}
+
 impl DanmuMessage {
   pub fn default_message() -> Self {
     DanmuMessage {
@@ -198,15 +370,26 @@ impl DanmuMessage {
         name: "哈哈哈".to_string(),
         streamer_name: "".to_string(),
         streamer_roomid: 0,
+        color: rgb(0x5C968E),
+        color_border: rgb(0x5C968E),
+        color_start: rgb(0x5C968E),
+        color_end: rgb(0x5C968E),
+        guard: GuardType::Captain,
+        is_lighted: true,
       }),
       ul: 37,
       ul_rank: "".to_string(),
       guard: GuardType::Captain,
+      mode: 1,
+      color: rgb(0xFFFFFF),
+      id_str: None,
+      emoticon: None,
+      reply_uname: None,
     }
   }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Getters, Serialize, Deserialize, TS)]
 #[ts(export)]
 #[ts(export_to = "frontend/src/bindings/Medal.ts")]
 pub struct Medal {
@@ -214,9 +397,19 @@ pub struct Medal {
   name: String,
   streamer_name: String,
   streamer_roomid: u64,
+  // colors of the badge, e.g. "#5C968E"
+  color: String,
+  color_border: String,
+  color_start: String,
+  color_end: String,
+  // the owner's guard level in the medal's room
+  guard: GuardType,
+  // medals not lighted are shown in grey
+  is_lighted: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+/// Ordered by rank, from no guard up to governor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, TS)]
 #[ts(export)]
 #[ts(export_to = "frontend/src/bindings/GuardType.ts")]
 pub enum GuardType {
@@ -255,9 +448,73 @@ pub struct GiftMessage {
   // type is still Number.
   #[ts(type = "number")]
   gift_num: u64,
+  // price of one gift, in coins of `coin_type`
+  #[ts(type = "number")]
+  price: u64,
+  coin_type: CoinType,
+  // price of all the gifts sent, in coins of `coin_type`
+  #[ts(type = "number")]
+  total_coin: u64,
+  // id of the SEND_GIFT transaction, COMBO_SEND doesn't have one
+  tid: Option<String>,
+  // shared by the gifts sent in one batch combo
+  batch_combo_id: Option<String>,
+  // the blind box opened, if the gift comes from one
+  blind_gift: Option<BlindGift>,
+  // the streamer who receives the gift
+  receiver: Option<GiftReceiver>,
+  // 勋章，可能未佩戴
+  #[getter(skip)]
+  medal: Option<Medal>,
+}
+
+/// What a gift is paid with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/CoinType.ts")]
+pub enum CoinType {
+  // 金瓜子, 1000 for 1 CNY
+  Gold,
+  // 银瓜子, free
+  Silver,
+}
+
+/// The blind box (盲盒) a gift is opened from
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/BlindGift.ts")]
+pub struct BlindGift {
+  #[ts(type = "number")]
+  original_gift_id: u64,
+  original_gift_name: String,
+  // price of the blind box, in gold coins
+  #[ts(type = "number")]
+  original_gift_price: u64,
+}
+
+/// The streamer who receives a gift
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/GiftReceiver.ts")]
+pub struct GiftReceiver {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
 }
 
 impl GiftMessage {
+  pub fn medal(&self) -> Option<&Medal> {
+    self.medal.as_ref()
+  }
+
+  /// what the gifts cost the sender in CNY, 0 for silver gifts
+  pub fn total_value_in_cny(&self) -> f64 {
+    match self.coin_type {
+      CoinType::Gold => self.total_coin as f64 / 1000.0,
+      CoinType::Silver => 0.0,
+    }
+  }
+
   fn from_raw(value: &NotificationBody) -> Option<GiftMessage> {
     assert_eq!("SEND_GIFT", value.get("cmd")?.as_str()?);
 
@@ -267,11 +524,42 @@ impl GiftMessage {
     let uname = data.get("uname")?.as_str()?.to_string();
     let guard: GuardType = data.get("guard_level")?.as_u64()?.into();
 
-    // gift info
-    let combo_send_info = data.get("combo_send")?;
-    let gift_id = combo_send_info.get("gift_id")?.as_u64()?;
-    let gift_name = combo_send_info.get("gift_name")?.as_str()?.to_string();
-    let gift_num = combo_send_info.get("gift_num")?.as_u64()?;
+    // gift info, combo_send is null for gifts not sent in a combo
+    let combo_send_info = data.get("combo_send").filter(|info| !info.is_null());
+    let gift_id = data
+      .get("giftId")
+      .and_then(Value::as_u64)
+      .or_else(|| combo_send_info?.get("gift_id")?.as_u64())?;
+    let gift_name = data
+      .get("giftName")
+      .and_then(Value::as_str)
+      .or_else(|| combo_send_info?.get("gift_name")?.as_str())?
+      .to_string();
+    let gift_num = data
+      .get("num")
+      .and_then(Value::as_u64)
+      .or_else(|| combo_send_info?.get("gift_num")?.as_u64())?;
+
+    // value info
+    let price = data.get("price").and_then(Value::as_u64).unwrap_or(0);
+    let coin_type = match data.get("coin_type").and_then(Value::as_str) {
+      Some("silver") => CoinType::Silver,
+      _ => CoinType::Gold,
+    };
+    let total_coin = data
+      .get("total_coin")
+      .and_then(Value::as_u64)
+      .unwrap_or_else(|| price.saturating_mul(gift_num));
+    let blind_gift = data.get("blind_gift").and_then(|blind_gift| {
+      Some(BlindGift {
+        original_gift_id: blind_gift.get("original_gift_id")?.as_u64()?,
+        original_gift_name: blind_gift.get("original_gift_name")?.as_str()?.to_string(),
+        original_gift_price: blind_gift
+          .get("original_gift_price")
+          .and_then(Value::as_u64)
+          .unwrap_or(0),
+      })
+    });
 
     Some(GiftMessage {
       uid,
@@ -280,6 +568,17 @@ impl GiftMessage {
       gift_id,
       gift_name,
       gift_num,
+      price,
+      coin_type,
+      total_coin,
+      tid: data.get("tid").and_then(|tid| match tid {
+        Value::String(tid) => Some(tid.clone()),
+        tid => tid.as_u64().map(|tid| tid.to_string()),
+      }),
+      batch_combo_id: batch_combo_id(data),
+      blind_gift,
+      receiver: GiftReceiver::from_raw(data),
+      medal: data.get("medal_info").and_then(Medal::from_medal_info),
     })
   }
 
@@ -297,6 +596,13 @@ impl GiftMessage {
     let gift_name = data.get("gift_name")?.as_str()?.to_string();
     let gift_num = data.get("combo_num")?.as_u64()?;
 
+    // value info, only gold gifts are sent in combos
+    let total_coin = data
+      .get("combo_total_coin")
+      .and_then(Value::as_u64)
+      .unwrap_or(0);
+    let price = total_coin.checked_div(gift_num).unwrap_or(0);
+
     Some(GiftMessage {
       uid,
       uname,
@@ -304,10 +610,65 @@ impl GiftMessage {
       gift_id,
       gift_name,
       gift_num,
+      price,
+      coin_type: CoinType::Gold,
+      total_coin,
+      tid: None,
+      batch_combo_id: batch_combo_id(data),
+      blind_gift: None,
+      receiver: GiftReceiver::from_raw(data),
+      medal: data.get("medal_info").and_then(Medal::from_medal_info),
+    })
+  }
+}
+
+impl Medal {
+  // parse the medal object sent with interactions and gifts,
+  // 勋章等级为0表示未佩戴
+  fn from_medal_info(medal: &Value) -> Option<Medal> {
+    let level = medal.get("medal_level")?.as_u64()?;
+    if level == 0 {
+      return None;
+    }
+    let number = |field: &str| medal.get(field).and_then(Value::as_u64).unwrap_or(0);
+    Some(Medal {
+      level,
+      name: medal.get("medal_name")?.as_str()?.to_string(),
+      // the streamer's name is not sent
+      streamer_name: "".to_string(),
+      streamer_roomid: medal
+        .get("anchor_roomid")
+        .and_then(Value::as_u64)
+        .unwrap_or(0),
+      color: rgb(number("medal_color")),
+      color_border: rgb(number("medal_color_border")),
+      color_start: rgb(number("medal_color_start")),
+      color_end: rgb(number("medal_color_end")),
+      guard: number("guard_level").into(),
+      is_lighted: number("is_lighted") == 1,
+    })
+  }
+}
+
+impl GiftReceiver {
+  fn from_raw(data: &Value) -> Option<GiftReceiver> {
+    let receiver = data.get("receive_user_info")?;
+    Some(GiftReceiver {
+      uid: receiver.get("uid")?.as_u64()?,
+      uname: receiver.get("uname")?.as_str()?.to_string(),
     })
   }
 }
 
+// empty if the gifts are not sent in a batch combo
+fn batch_combo_id(data: &Value) -> Option<String> {
+  data
+    .get("batch_combo_id")
+    .and_then(Value::as_str)
+    .filter(|id| !id.is_empty())
+    .map(String::from)
+}
+
 impl GiftMessage {
   pub fn default_message() -> GiftMessage {
     GiftMessage {
@@ -317,12 +678,909 @@ impl GiftMessage {
       gift_id: 0,
       gift_name: "小花花".to_string(),
       gift_num: 1,
+      price: 100,
+      coin_type: CoinType::Gold,
+      total_coin: 100,
+      tid: None,
+      batch_combo_id: None,
+      blind_gift: None,
+      receiver: None,
+      medal: None,
+    }
+  }
+}
+
+/// The type representing a Super Chat (醒目留言)
+#[derive(Debug, Clone, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/SuperChatMessage.ts")]
+pub struct SuperChatMessage {
+  // id of the super chat, referred to by deletions
+  #[ts(type = "number")]
+  id: u64,
+  // sender's info
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  guard: GuardType,
+  // price in CNY
+  #[ts(type = "number")]
+  price: u64,
+  message: String,
+  // translation of the message, None if not translated
+  message_trans: Option<String>,
+  // how long the super chat stays pinned, in seconds
+  #[ts(type = "number")]
+  duration: u64,
+  // timestamp the super chat is sent, in seconds
+  #[ts(type = "number")]
+  start_time: u64,
+  // colors of the card, e.g. "#EDF5FF"
+  background_color: String,
+  background_bottom_color: String,
+  background_price_color: String,
+  message_font_color: String,
+}
+
+impl SuperChatMessage {
+  pub fn default_message() -> SuperChatMessage {
+    SuperChatMessage {
+      id: 0,
+      uid: 0,
+      uname: "测试用户".to_string(),
+      guard: GuardType::NoGuard,
+      price: 30,
+      message: "主播今天唱的歌太好听了, 明天还来!".to_string(),
+      message_trans: None,
+      duration: 60,
+      start_time: 0,
+      background_color: "#EDF5FF".to_string(),
+      background_bottom_color: "#2A60B2".to_string(),
+      background_price_color: "#7497CD".to_string(),
+      message_font_color: "#A3F6FF".to_string(),
+    }
+  }
+
+  fn from_raw(value: &NotificationBody) -> Option<SuperChatMessage> {
+    let data = value.get("data")?;
+    let user_info = data.get("user_info")?;
+    let message_trans = data
+      .get("message_trans")
+      .and_then(Value::as_str)
+      .filter(|trans| !trans.is_empty())
+      .map(str::to_string);
+
+    Some(SuperChatMessage {
+      id: as_u64_or_str(data.get("id")?)?,
+      uid: as_u64_or_str(data.get("uid")?)?,
+      uname: user_info.get("uname")?.as_str()?.to_string(),
+      guard: user_info
+        .get("guard_level")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+        .into(),
+      price: data.get("price")?.as_u64()?,
+      message: data.get("message")?.as_str()?.to_string(),
+      message_trans,
+      duration: data.get("time").and_then(Value::as_u64).unwrap_or(0),
+      start_time: data.get("start_time").and_then(Value::as_u64).unwrap_or(0),
+      background_color: color(data, "background_color"),
+      background_bottom_color: color(data, "background_bottom_color"),
+      background_price_color: color(data, "background_price_color"),
+      message_font_color: color(data, "message_font_color"),
+    })
+  }
+
+  // ids of the deleted super chats
+  fn deleted_ids(value: &NotificationBody) -> Option<Vec<u64>> {
+    let ids = value.get("data")?.get("ids")?.as_array()?;
+    Some(ids.iter().filter_map(as_u64_or_str).collect())
+  }
+}
+
+/// What a viewer did in an [InteractionMessage]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/InteractionType.ts")]
+pub enum InteractionType {
+  // 进入直播间
+  Enter,
+  // 关注
+  Follow,
+  // 分享直播间
+  Share,
+  // 特别关注
+  SpecialFollow,
+  // 互相关注
+  MutualFollow,
+}
+
+impl InteractionType {
+  fn from_msg_type(msg_type: u64) -> Option<InteractionType> {
+    match msg_type {
+      1 => Some(InteractionType::Enter),
+      2 => Some(InteractionType::Follow),
+      3 => Some(InteractionType::Share),
+      4 => Some(InteractionType::SpecialFollow),
+      5 => Some(InteractionType::MutualFollow),
+      _ => None,
+    }
+  }
+}
+
+/// The type representing a viewer's interaction with the room (INTERACT_WORD)
+#[derive(Debug, Clone, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/InteractionMessage.ts")]
+pub struct InteractionMessage {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  kind: InteractionType,
+  // 勋章，可能未佩戴
+  #[getter(skip)]
+  medal: Option<Medal>,
+  // 舰队身份
+  guard: GuardType,
+  // timestamp of the interaction, in seconds
+  #[ts(type = "number")]
+  timestamp: u64,
+}
+
+impl InteractionMessage {
+  pub fn medal(&self) -> Option<&Medal> {
+    self.medal.as_ref()
+  }
+
+  fn from_raw(value: &NotificationBody) -> Option<InteractionMessage> {
+    let data = value.get("data")?;
+    let kind = InteractionType::from_msg_type(data.get("msg_type")?.as_u64()?)?;
+
+    let medal = data.get("fans_medal").and_then(Medal::from_medal_info);
+
+    Some(InteractionMessage {
+      uid: data.get("uid")?.as_u64()?,
+      uname: data.get("uname")?.as_str()?.to_string(),
+      kind,
+      medal,
+      // privilege_type is the guard level
+      guard: data
+        .get("privilege_type")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+        .into(),
+      timestamp: data.get("timestamp").and_then(Value::as_u64).unwrap_or(0),
+    })
+  }
+}
+
+/// The type representing a guard member's entrance (ENTRY_EFFECT, WELCOME_GUARD)
+#[derive(Debug, Clone, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/GuardEntryMessage.ts")]
+pub struct GuardEntryMessage {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  guard: GuardType,
+  // official welcome text with highlight markup removed,
+  // e.g. "欢迎舰长 xxx 进入直播间"
+  copy_writing: String,
+  // None for WELCOME_GUARD, which carries no effect
+  effect: Option<EntryEffect>,
+}
+
+/// Metadata of an entrance effect
+#[derive(Debug, Clone, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/EntryEffect.ts")]
+pub struct EntryEffect {
+  #[ts(type = "number")]
+  id: u64,
+  // effects with higher priority are shown first
+  #[ts(type = "number")]
+  priority: u64,
+  // colors of the text and the highlighted user name, e.g. "#ffffff"
+  copy_color: String,
+  highlight_color: String,
+  // background image of the effect
+  basemap_url: String,
+  // how long the effect is shown, in seconds
+  #[ts(type = "number")]
+  effective_time: u64,
+}
+
+impl GuardEntryMessage {
+  fn from_raw_entry_effect(value: &NotificationBody) -> Option<GuardEntryMessage> {
+    let data = value.get("data")?;
+    let raw_copy_writing = data.get("copy_writing")?.as_str()?;
+    let str_field = |field: &str| {
+      data
+        .get(field)
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string()
+    };
+    let effect = EntryEffect {
+      id: data.get("id").and_then(Value::as_u64).unwrap_or(0),
+      priority: data.get("priority").and_then(Value::as_u64).unwrap_or(0),
+      copy_color: str_field("copy_color"),
+      highlight_color: str_field("highlight_color"),
+      basemap_url: str_field("web_basemap_url"),
+      effective_time: data
+        .get("web_effective_time")
+        .or_else(|| data.get("effective_time"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0),
+    };
+
+    Some(GuardEntryMessage {
+      uid: data.get("uid")?.as_u64()?,
+      // the user name is the highlighted part of the text
+      uname: highlighted(raw_copy_writing).unwrap_or("").to_string(),
+      guard: guard_of_effect(value),
+      copy_writing: strip_highlight(raw_copy_writing),
+      effect: Some(effect),
+    })
+  }
+
+  fn from_raw_welcome_guard(value: &NotificationBody) -> Option<GuardEntryMessage> {
+    let data = value.get("data")?;
+    let uname = data.get("username")?.as_str()?.to_string();
+    let guard: GuardType = data.get("guard_level")?.as_u64()?.into();
+    let title = match guard {
+      GuardType::Governor => "总督",
+      GuardType::Admiral => "提督",
+      _ => "舰长",
+    };
+
+    Some(GuardEntryMessage {
+      uid: data.get("uid")?.as_u64()?,
+      copy_writing: format!("欢迎{title} {uname} 进入直播间"),
+      uname,
+      guard,
+      effect: None,
+    })
+  }
+}
+
+// the first part of the text wrapped in <% %>
+fn highlighted(text: &str) -> Option<&str> {
+  let start = text.find("<%")? + 2;
+  let end = start + text[start..].find("%>")?;
+  Some(&text[start..end])
+}
+
+// remove the <% %> highlight markup
+fn strip_highlight(text: &str) -> String {
+  text.replace("<%", "").replace("%>", "")
+}
+
+/// The type representing a guard (舰长/提督/总督) purchase or renewal
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/GuardPurchaseMessage.ts")]
+pub struct GuardPurchaseMessage {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  guard: GuardType,
+  // number of months bought
+  #[ts(type = "number")]
+  months: u64,
+  // price in CNY
+  #[ts(type = "number")]
+  price: u64,
+  // renewal of an existing guard, only known from USER_TOAST_MSG
+  is_renewal: bool,
+}
+
+impl GuardPurchaseMessage {
+  pub fn default_message() -> GuardPurchaseMessage {
+    GuardPurchaseMessage {
+      uid: 0,
+      uname: "测试用户".to_string(),
+      guard: GuardType::Captain,
+      months: 1,
+      price: 198,
+      is_renewal: false,
     }
   }
+
+  fn from_raw_guard_buy(value: &NotificationBody) -> Option<GuardPurchaseMessage> {
+    let data = value.get("data")?;
+    Some(GuardPurchaseMessage {
+      uid: data.get("uid")?.as_u64()?,
+      uname: data.get("username")?.as_str()?.to_string(),
+      guard: data.get("guard_level")?.as_u64()?.into(),
+      months: data.get("num").and_then(Value::as_u64).unwrap_or(1),
+      // price is in gold coins (金瓜子), 1000 for 1 CNY
+      price: data.get("price").and_then(Value::as_u64).unwrap_or(0) / 1000,
+      is_renewal: false,
+    })
+  }
+
+  fn from_raw_toast(value: &NotificationBody) -> Option<GuardPurchaseMessage> {
+    let data = value.get("data")?;
+    // op_type: 1 -> 开通, 2 -> 续费, 3 -> 自动续费
+    let op_type = data.get("op_type").and_then(Value::as_u64).unwrap_or(1);
+    Some(GuardPurchaseMessage {
+      uid: data.get("uid")?.as_u64()?,
+      uname: data.get("username")?.as_str()?.to_string(),
+      guard: data.get("guard_level")?.as_u64()?.into(),
+      months: data.get("num").and_then(Value::as_u64).unwrap_or(1),
+      price: data.get("price").and_then(Value::as_u64).unwrap_or(0) / 1000,
+      is_renewal: op_type == 2 || op_type == 3,
+    })
+  }
+}
+
+/// A viewer on the online rank (高能榜)
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/OnlineRankUser.ts")]
+pub struct OnlineRankUser {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  // avatar url
+  face: String,
+  // 1 for the top viewer
+  rank: u32,
+  // contribution of the viewer, in gold coins
+  #[ts(type = "number")]
+  score: u64,
+  guard: GuardType,
+}
+
+// only the top viewers of the online rank are forwarded
+const ONLINE_RANK_TOP: usize = 3;
+
+impl OnlineRankUser {
+  fn from_raw_rank(value: &NotificationBody) -> Option<Vec<OnlineRankUser>> {
+    let data = value.get("data")?;
+    // newer servers send the rank as online_list
+    let list = data.get("online_list").or_else(|| data.get("list"))?;
+    let mut users: Vec<OnlineRankUser> = list
+      .as_array()?
+      .iter()
+      .filter_map(|user| {
+        Some(OnlineRankUser {
+          uid: user.get("uid")?.as_u64()?,
+          uname: user.get("uname")?.as_str()?.to_string(),
+          face: user
+            .get("face")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string(),
+          rank: user.get("rank")?.as_u64()? as u32,
+          score: user.get("score").and_then(as_u64_or_str).unwrap_or(0),
+          guard: user
+            .get("guard_level")
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+            .into(),
+        })
+      })
+      .collect();
+    users.sort_by_key(|user| user.rank);
+    users.truncate(ONLINE_RANK_TOP);
+    Some(users)
+  }
+}
+
+/// A red pocket (红包抽奖) sent to the room (POPULARITY_RED_POCKET_START)
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/RedPocketMessage.ts")]
+pub struct RedPocketMessage {
+  #[ts(type = "number")]
+  lot_id: u64,
+  // who sends the red pocket
+  #[ts(type = "number")]
+  sender_uid: u64,
+  sender_name: String,
+  // the danmu sent to join
+  danmu: String,
+  // when the winners are drawn, in seconds
+  #[ts(type = "number")]
+  start_time: u64,
+  #[ts(type = "number")]
+  end_time: u64,
+  awards: Vec<RedPocketAward>,
+}
+
+/// Gifts in a red pocket
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/RedPocketAward.ts")]
+pub struct RedPocketAward {
+  #[ts(type = "number")]
+  gift_id: u64,
+  gift_name: String,
+  #[ts(type = "number")]
+  num: u64,
+}
+
+/// A lottery (天选时刻) started by the streamer (ANCHOR_LOT_START)
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/AnchorLotteryMessage.ts")]
+pub struct AnchorLotteryMessage {
+  #[ts(type = "number")]
+  id: u64,
+  award_name: String,
+  #[ts(type = "number")]
+  award_num: u64,
+  // the danmu sent to join
+  danmu: String,
+  // who may join, e.g. "关注主播"
+  require_text: String,
+  // gift to send to join, None if joining is free
+  gift_name: Option<String>,
+  #[ts(type = "number")]
+  gift_num: u64,
+  // how long the lottery takes participants, in seconds
+  #[ts(type = "number")]
+  max_time: u64,
+}
+
+/// A viewer who wins a red pocket or a lottery
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/LotteryWinner.ts")]
+pub struct LotteryWinner {
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+  award_name: String,
+}
+
+impl RedPocketMessage {
+  fn from_raw(value: &NotificationBody) -> Option<RedPocketMessage> {
+    let data = value.get("data")?;
+    let number = |field: &str| data.get(field).and_then(Value::as_u64).unwrap_or(0);
+    let awards = data
+      .get("awards")
+      .and_then(Value::as_array)
+      .map(|awards| {
+        awards
+          .iter()
+          .filter_map(|award| {
+            Some(RedPocketAward {
+              gift_id: award.get("gift_id")?.as_u64()?,
+              gift_name: award.get("gift_name")?.as_str()?.to_string(),
+              num: award.get("num").and_then(Value::as_u64).unwrap_or(1),
+            })
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Some(RedPocketMessage {
+      lot_id: data.get("lot_id")?.as_u64()?,
+      sender_uid: number("sender_uid"),
+      sender_name: data
+        .get("sender_name")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string(),
+      danmu: data.get("danmu")?.as_str()?.to_string(),
+      start_time: number("start_time"),
+      end_time: number("end_time"),
+      awards,
+    })
+  }
+
+  // winner_info holds [uid, uname, record id, award id] of each winner,
+  // the awards are looked up by id in awards
+  fn winners_from_raw(value: &NotificationBody) -> Option<(u64, Vec<LotteryWinner>)> {
+    let data = value.get("data")?;
+    let lot_id = data.get("lot_id")?.as_u64()?;
+    let awards = data.get("awards");
+    let winners = data
+      .get("winner_info")?
+      .as_array()?
+      .iter()
+      .filter_map(|winner| {
+        let award_id = winner.get(3).and_then(as_u64_or_str).unwrap_or(0);
+        let award_name = awards
+          .and_then(|awards| awards.get(award_id.to_string()))
+          .and_then(|award| award.get("award_name"))
+          .and_then(Value::as_str)
+          .unwrap_or("")
+          .to_string();
+        Some(LotteryWinner {
+          uid: winner.get(0)?.as_u64()?,
+          uname: winner.get(1)?.as_str()?.to_string(),
+          award_name,
+        })
+      })
+      .collect();
+    Some((lot_id, winners))
+  }
+}
+
+impl AnchorLotteryMessage {
+  fn from_raw(value: &NotificationBody) -> Option<AnchorLotteryMessage> {
+    let data = value.get("data")?;
+    let str_field = |field: &str| {
+      data
+        .get(field)
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string()
+    };
+    let number = |field: &str| data.get(field).and_then(Value::as_u64).unwrap_or(0);
+
+    Some(AnchorLotteryMessage {
+      id: data.get("id")?.as_u64()?,
+      award_name: data.get("award_name")?.as_str()?.to_string(),
+      award_num: number("award_num"),
+      danmu: str_field("danmu"),
+      require_text: str_field("require_text"),
+      // gift_id is 0 if no gift is required
+      gift_name: (number("gift_id") != 0).then(|| str_field("gift_name")),
+      gift_num: number("gift_num"),
+      max_time: number("max_time"),
+    })
+  }
+
+  fn award_from_raw(value: &NotificationBody) -> Option<BiliMessage> {
+    let data = value.get("data")?;
+    let award_name = data.get("award_name")?.as_str()?.to_string();
+    let winners = data
+      .get("award_users")
+      .and_then(Value::as_array)
+      .map(|users| {
+        users
+          .iter()
+          .filter_map(|user| {
+            Some(LotteryWinner {
+              uid: user.get("uid")?.as_u64()?,
+              uname: user.get("uname")?.as_str()?.to_string(),
+              award_name: award_name.clone(),
+            })
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Some(BiliMessage::AnchorLotteryAward {
+      id: data.get("id")?.as_u64()?,
+      award_name,
+      winners,
+    })
+  }
+}
+
+/// Progress of a PK battle between two rooms. The room that started the
+/// PK is `init`, the one matched with it is `matched`, either can be ours
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PkEvent.ts")]
+#[serde(tag = "kind")]
+pub enum PkEvent {
+  /// The opponent is found, the PK starts soon
+  Pre {
+    #[ts(type = "number")]
+    pk_id: u64,
+    opponent: PkOpponent,
+  },
+  /// The PK starts
+  Start {
+    #[ts(type = "number")]
+    pk_id: u64,
+    #[ts(type = "number")]
+    init_room_id: u64,
+    #[ts(type = "number")]
+    matched_room_id: u64,
+    // in seconds
+    #[ts(type = "number")]
+    start_time: u64,
+    #[ts(type = "number")]
+    end_time: u64,
+  },
+  /// The scores change
+  Progress {
+    #[ts(type = "number")]
+    pk_id: u64,
+    init: PkScore,
+    matched: PkScore,
+  },
+  /// The PK ends with the final scores
+  End {
+    #[ts(type = "number")]
+    pk_id: u64,
+    init: PkScore,
+    matched: PkScore,
+    // None for a draw
+    #[ts(type = "number | null")]
+    winner_room_id: Option<u64>,
+  },
+}
+
+/// The streamer a room is matched with in a PK
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PkOpponent.ts")]
+pub struct PkOpponent {
+  #[ts(type = "number")]
+  room_id: u64,
+  #[ts(type = "number")]
+  uid: u64,
+  uname: String,
+}
+
+/// Score of one room in a PK
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PkScore.ts")]
+pub struct PkScore {
+  #[ts(type = "number")]
+  room_id: u64,
+  #[ts(type = "number")]
+  votes: u64,
+  // who contributes the most votes
+  best_uname: String,
+}
+
+impl PkEvent {
+  // pk_id is sent as a number or a string at the top level
+  fn pk_id(value: &NotificationBody) -> Option<u64> {
+    as_u64_or_str(value.get("pk_id")?)
+  }
+
+  fn from_raw_pre(value: &NotificationBody) -> Option<PkEvent> {
+    let data = value.get("data")?;
+    Some(PkEvent::Pre {
+      pk_id: Self::pk_id(value)?,
+      opponent: PkOpponent {
+        room_id: data.get("room_id")?.as_u64()?,
+        uid: data.get("uid").and_then(Value::as_u64).unwrap_or(0),
+        uname: data.get("uname")?.as_str()?.to_string(),
+      },
+    })
+  }
+
+  fn from_raw_start(value: &NotificationBody) -> Option<PkEvent> {
+    let data = value.get("data")?;
+    Some(PkEvent::Start {
+      pk_id: Self::pk_id(value)?,
+      init_room_id: data.pointer("/init_info/room_id")?.as_u64()?,
+      matched_room_id: data.pointer("/match_info/room_id")?.as_u64()?,
+      start_time: data
+        .get("pk_start_time")
+        .and_then(Value::as_u64)
+        .unwrap_or(0),
+      end_time: data.get("pk_end_time").and_then(Value::as_u64).unwrap_or(0),
+    })
+  }
+
+  fn from_raw_process(value: &NotificationBody) -> Option<PkEvent> {
+    let data = value.get("data")?;
+    Some(PkEvent::Progress {
+      pk_id: Self::pk_id(value)?,
+      init: PkScore::from_raw(data.get("init_info")?)?,
+      matched: PkScore::from_raw(data.get("match_info")?)?,
+    })
+  }
+
+  fn from_raw_end(value: &NotificationBody) -> Option<PkEvent> {
+    let data = value.get("data")?;
+    let (init_info, match_info) = (data.get("init_info")?, data.get("match_info")?);
+    // winner_type: 2 -> win, -1 -> lose, the same on both sides for a draw
+    let winner_type = |info: &Value| info.get("winner_type").and_then(Value::as_i64).unwrap_or(0);
+    let init = PkScore::from_raw(init_info)?;
+    let matched = PkScore::from_raw(match_info)?;
+    let winner_room_id = match winner_type(init_info).cmp(&winner_type(match_info)) {
+      std::cmp::Ordering::Greater => Some(init.room_id),
+      std::cmp::Ordering::Less => Some(matched.room_id),
+      std::cmp::Ordering::Equal => None,
+    };
+    Some(PkEvent::End {
+      pk_id: Self::pk_id(value)?,
+      init,
+      matched,
+      winner_room_id,
+    })
+  }
+}
+
+impl PkScore {
+  fn from_raw(info: &Value) -> Option<PkScore> {
+    Some(PkScore {
+      room_id: info.get("room_id")?.as_u64()?,
+      votes: info.get("votes").and_then(Value::as_u64).unwrap_or(0),
+      best_uname: info
+        .get("best_uname")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string(),
+    })
+  }
+}
+
+/// Result of a PK for our room
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PkResult.ts")]
+pub enum PkResult {
+  Win,
+  Lose,
+  Draw,
+}
+
+/// The latest PK of a room, seen from the room's side
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PkSession.ts")]
+pub struct PkSession {
+  #[ts(type = "number")]
+  pk_id: u64,
+  #[ts(type = "number")]
+  opponent_room_id: u64,
+  // empty if the PK is joined after it is matched
+  opponent_uname: String,
+  #[ts(type = "number")]
+  score: u64,
+  #[ts(type = "number")]
+  opponent_score: u64,
+  // when the PK ends, in seconds, 0 until it starts
+  #[ts(type = "number")]
+  end_time: u64,
+  // None until the PK ends
+  result: Option<PkResult>,
+}
+
+impl PkSession {
+  /// update the PK of the given room with an event, a new PK replaces the old one
+  pub fn update(session: &mut Option<PkSession>, room_id: u64, event: &PkEvent) {
+    let pk_id = match event {
+      PkEvent::Pre { pk_id, .. }
+      | PkEvent::Start { pk_id, .. }
+      | PkEvent::Progress { pk_id, .. }
+      | PkEvent::End { pk_id, .. } => *pk_id,
+    };
+    if session.as_ref().map(|session| session.pk_id) != Some(pk_id) {
+      *session = Some(PkSession {
+        pk_id,
+        ..Default::default()
+      });
+    }
+    let Some(session) = session.as_mut() else {
+      return;
+    };
+
+    match event {
+      PkEvent::Pre { opponent, .. } => {
+        session.opponent_room_id = opponent.room_id;
+        session.opponent_uname = opponent.uname.clone();
+      }
+      PkEvent::Start {
+        init_room_id,
+        matched_room_id,
+        end_time,
+        ..
+      } => {
+        session.opponent_room_id = if *init_room_id == room_id {
+          *matched_room_id
+        } else {
+          *init_room_id
+        };
+        session.end_time = *end_time;
+      }
+      PkEvent::Progress { init, matched, .. } => session.set_scores(room_id, init, matched),
+      PkEvent::End {
+        init,
+        matched,
+        winner_room_id,
+        ..
+      } => {
+        session.set_scores(room_id, init, matched);
+        session.result = Some(match winner_room_id {
+          None => PkResult::Draw,
+          Some(winner) if *winner == room_id => PkResult::Win,
+          Some(_) => PkResult::Lose,
+        });
+      }
+    }
+  }
+
+  fn set_scores(&mut self, room_id: u64, init: &PkScore, matched: &PkScore) {
+    let (ours, theirs) = if matched.room_id == room_id {
+      (matched, init)
+    } else {
+      (init, matched)
+    };
+    self.opponent_room_id = theirs.room_id;
+    self.score = ours.votes;
+    self.opponent_score = theirs.votes;
+  }
+}
+
+/// Changes of the live room itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/RoomEvent.ts")]
+#[serde(tag = "kind")]
+pub enum RoomEvent {
+  /// The streamer started streaming,
+  /// Bilibili usually sends it twice and only one carries `live_time`
+  Live {
+    #[ts(type = "number | null")]
+    live_time: Option<u64>,
+  },
+  /// The stream ended
+  Preparing,
+  /// The streamer changed the title or the area of the room
+  RoomChange {
+    title: String,
+    area_name: String,
+    parent_area_name: String,
+  },
+  /// The stream was cut off by moderators (切断直播)
+  CutOff { msg: String },
+  /// The room received a warning from moderators (警告)
+  Warning { msg: String },
+}
+
+impl RoomEvent {
+  fn from_raw_live(value: &NotificationBody) -> Option<RoomEvent> {
+    Some(RoomEvent::Live {
+      live_time: value.get("live_time").and_then(as_u64_or_str),
+    })
+  }
+
+  fn from_raw_room_change(value: &NotificationBody) -> Option<RoomEvent> {
+    let data = value.get("data")?;
+    let str_field = |field: &str| {
+      data
+        .get(field)
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string()
+    };
+    Some(RoomEvent::RoomChange {
+      title: data.get("title")?.as_str()?.to_string(),
+      area_name: str_field("area_name"),
+      parent_area_name: str_field("parent_area_name"),
+    })
+  }
+
+  fn from_raw_msg(value: &NotificationBody) -> Option<String> {
+    Some(value.get("msg")?.as_str()?.to_string())
+  }
+}
+
+// guard level of the user an ENTRY_EFFECT is shown for
+fn guard_of_effect(value: &NotificationBody) -> GuardType {
+  value
+    .pointer("/data/privilege_type")
+    .and_then(Value::as_u64)
+    .unwrap_or(0)
+    .into()
+}
+
+// some numbers are sent as strings
+fn as_u64_or_str(value: &Value) -> Option<u64> {
+  value
+    .as_u64()
+    .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+// color field of a super chat, empty if missing
+fn color(data: &Value, field: &str) -> String {
+  data
+    .get(field)
+    .and_then(Value::as_str)
+    .unwrap_or("")
+    .to_string()
 }
 
 impl BiliMessage {
-  /// convert from websocket message body
+  /// convert from websocket message body, notifications of unknown
+  /// commands are converted to [BiliMessage::Raw]
   pub(crate) fn from_raw_wesocket_message(msg: BiliWebsocketInner) -> Option<BiliMessage> {
     let body = msg.into_body();
     match body {
@@ -330,41 +1588,796 @@ impl BiliMessage {
         Some(BiliMessage::RoomPopularity(popularity))
       }
       super::BiliWebsocketMessageBody::Notification(notification) => {
-        let cmd = notification.get("cmd")?;
-        let cmd = cmd.as_str()?;
-        // Current Commands:
-        // Reference: https://github.com/lovelyyoshino/Bilibili-Live-API/blob/master/API.WebSocket.md
-        // "DANMU_MSG": 弹幕
-        // (欢迎消息触发不稳定，可能有缓存时间)
-        // "ENTRY_EFFECT": 欢迎舰长
-        // "WELCOME": 欢迎
-        // "SUPER_CHAT_MESSAGE_JPN"
-        // "SUPER_CHAT": SC
-        //
-        // "SEND_GIFT": 投喂礼物
-        // "COMBO_SEND": 连击投喂 (不知道怎么触发)
-        //
-        // "GUARD_BUY": 上舰长
-        // "USER_TOAST_MSG": 续费了舰长
-        // "NOTICE_MSG": 本房间续费舰长
-        //
-        // 其他暂时不支持
-        match cmd {
-          "DANMU_MSG" => DanmuMessage::from_raw(&notification).map(BiliMessage::Danmu),
-
-          "SEND_GIFT" => GiftMessage::from_raw(&notification).map(BiliMessage::Gift),
-
-          "COMBO_SEND" => GiftMessage::from_raw_combo(&notification).map(BiliMessage::Gift),
-
-          "INTERACT_WORD" => None,
-
-          _ => {
-            // println!("{:?}", serde_json::to_string_pretty(&notification));
-            None
-          }
-        }
+        Self::from_notification(notification)
       }
       super::BiliWebsocketMessageBody::EntryReply => None,
     }
   }
+
+  /// convert from notification body, None if it is malformed
+  fn from_notification(raw: NotificationBody) -> Option<BiliMessage> {
+    let cmd = raw.get("cmd")?.as_str()?.to_string();
+    let notification = &raw;
+    // Current Commands:
+    // Reference: https://github.com/lovelyyoshino/Bilibili-Live-API/blob/master/API.WebSocket.md
+    // "DANMU_MSG": 弹幕
+    // (欢迎消息触发不稳定，可能有缓存时间)
+    // "ENTRY_EFFECT": 欢迎舰长
+    // "WELCOME": 欢迎
+    // "SUPER_CHAT_MESSAGE": SC
+    // "SUPER_CHAT_MESSAGE_JPN": SC (日文翻译版, 与SUPER_CHAT_MESSAGE同时发送, id相同)
+    // "SUPER_CHAT_MESSAGE_DELETE": SC被删除
+    //
+    // "SEND_GIFT": 投喂礼物
+    // "COMBO_SEND": 连击投喂 (不知道怎么触发)
+    //
+    // "GUARD_BUY": 上舰长
+    // "USER_TOAST_MSG": 续费了舰长
+    // "NOTICE_MSG": 本房间续费舰长
+    //
+    // "ONLINE_RANK_COUNT": 高能榜人数
+    // "ONLINE_RANK_V2": 高能榜前几名
+    // "WATCHED_CHANGE": 看过人数
+    // "LIKE_INFO_V3_UPDATE": 点赞数
+    //
+    // "POPULARITY_RED_POCKET_START": 红包抽奖开始
+    // "POPULARITY_RED_POCKET_WINNER_LIST": 红包抽奖中奖名单
+    // "ANCHOR_LOT_START": 天选时刻开始
+    // "ANCHOR_LOT_END": 天选时刻结束
+    // "ANCHOR_LOT_AWARD": 天选时刻中奖名单
+    //
+    // "PK_BATTLE_PRE_NEW": PK匹配成功
+    // "PK_BATTLE_START_NEW": PK开始
+    // "PK_BATTLE_PROCESS_NEW": PK比分变化
+    // "PK_BATTLE_END": PK结束
+    //
+    // "LIVE": 开播
+    // "PREPARING": 下播
+    // "ROOM_CHANGE": 更换标题或分区
+    // "CUT_OFF": 被超管切断直播
+    // "WARNING": 被超管警告
+    //
+    // 其他暂时不支持, 作为Raw转发
+    match cmd.as_str() {
+      "DANMU_MSG" => DanmuMessage::from_raw(notification).map(BiliMessage::Danmu),
+
+      "SEND_GIFT" => GiftMessage::from_raw(notification).map(BiliMessage::Gift),
+
+      "COMBO_SEND" => GiftMessage::from_raw_combo(notification).map(BiliMessage::Gift),
+
+      "SUPER_CHAT_MESSAGE" => SuperChatMessage::from_raw(notification).map(BiliMessage::SuperChat),
+
+      // a copy of the SUPER_CHAT_MESSAGE with the same id, whose translation
+      // is already in `message_trans`, so it isn't published again
+      "SUPER_CHAT_MESSAGE_JPN" => None,
+
+      "SUPER_CHAT_MESSAGE_DELETE" => {
+        SuperChatMessage::deleted_ids(notification).map(|ids| BiliMessage::SuperChatDelete { ids })
+      }
+
+      // the same purchase is sent as both GUARD_BUY and USER_TOAST_MSG,
+      // the client forwards only one of them
+      "GUARD_BUY" => {
+        GuardPurchaseMessage::from_raw_guard_buy(notification).map(BiliMessage::GuardPurchase)
+      }
+
+      "USER_TOAST_MSG" => {
+        GuardPurchaseMessage::from_raw_toast(notification).map(BiliMessage::GuardPurchase)
+      }
+
+      // effects of other users, e.g. high-ranked ones or medal holders, have
+      // privilege_type 0 and aren't guard entrances, they are dropped
+      "ENTRY_EFFECT" if guard_of_effect(notification) == GuardType::NoGuard => None,
+
+      "ENTRY_EFFECT" => {
+        GuardEntryMessage::from_raw_entry_effect(notification).map(BiliMessage::GuardEntry)
+      }
+
+      "WELCOME_GUARD" => {
+        GuardEntryMessage::from_raw_welcome_guard(notification).map(BiliMessage::GuardEntry)
+      }
+
+      "INTERACT_WORD" => InteractionMessage::from_raw(notification).map(BiliMessage::Interaction),
+
+      "ONLINE_RANK_COUNT" => {
+        let count = notification
+          .pointer("/data/count")
+          .and_then(as_u64_or_str)?;
+        Some(BiliMessage::OnlineRankCount { count })
+      }
+
+      "ONLINE_RANK_V2" => {
+        OnlineRankUser::from_raw_rank(notification).map(BiliMessage::OnlineRankTop)
+      }
+
+      "WATCHED_CHANGE" => {
+        let data = notification.get("data")?;
+        Some(BiliMessage::WatchedChange {
+          num: data.get("num").and_then(as_u64_or_str)?,
+          text: data
+            .get("text_large")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string(),
+        })
+      }
+
+      "LIKE_INFO_V3_UPDATE" => {
+        let count = notification
+          .pointer("/data/click_count")
+          .and_then(as_u64_or_str)?;
+        Some(BiliMessage::LikeCount { count })
+      }
+
+      "POPULARITY_RED_POCKET_START" => {
+        RedPocketMessage::from_raw(notification).map(BiliMessage::RedPocketStart)
+      }
+
+      "POPULARITY_RED_POCKET_WINNER_LIST" => RedPocketMessage::winners_from_raw(notification)
+        .map(|(lot_id, winners)| BiliMessage::RedPocketWinners { lot_id, winners }),
+
+      "ANCHOR_LOT_START" => {
+        AnchorLotteryMessage::from_raw(notification).map(BiliMessage::AnchorLotteryStart)
+      }
+
+      "ANCHOR_LOT_END" => {
+        let id = notification.pointer("/data/id").and_then(Value::as_u64)?;
+        Some(BiliMessage::AnchorLotteryEnd { id })
+      }
+
+      "ANCHOR_LOT_AWARD" => AnchorLotteryMessage::award_from_raw(notification),
+
+      // PK_BATTLE_PRE, PK_BATTLE_START and PK_BATTLE_PROCESS are sent along
+      // with their _NEW versions for old clients, only the latter are parsed
+      "PK_BATTLE_PRE_NEW" => PkEvent::from_raw_pre(notification).map(BiliMessage::Pk),
+
+      "PK_BATTLE_START_NEW" => PkEvent::from_raw_start(notification).map(BiliMessage::Pk),
+
+      "PK_BATTLE_PROCESS_NEW" => PkEvent::from_raw_process(notification).map(BiliMessage::Pk),
+
+      "PK_BATTLE_END" => PkEvent::from_raw_end(notification).map(BiliMessage::Pk),
+
+      "LIVE" => RoomEvent::from_raw_live(notification).map(BiliMessage::RoomEvent),
+
+      "PREPARING" => Some(BiliMessage::RoomEvent(RoomEvent::Preparing)),
+
+      "ROOM_CHANGE" => RoomEvent::from_raw_room_change(notification).map(BiliMessage::RoomEvent),
+
+      "CUT_OFF" => RoomEvent::from_raw_msg(notification)
+        .map(|msg| BiliMessage::RoomEvent(RoomEvent::CutOff { msg })),
+
+      "WARNING" => RoomEvent::from_raw_msg(notification)
+        .map(|msg| BiliMessage::RoomEvent(RoomEvent::Warning { msg })),
+
+      _ => Some(BiliMessage::Raw { cmd, body: raw }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::client::mock::LIVE_SESSION;
+
+  fn fixture(json: &str) -> BiliMessage {
+    let notification: NotificationBody = serde_json::from_str(json).unwrap();
+    BiliMessage::from_notification(notification).unwrap()
+  }
+
+  #[test]
+  fn test_super_chat() {
+    let BiliMessage::SuperChat(sc) = fixture(include_str!("fixtures/super_chat_message.json"))
+    else {
+      panic!("Not a Super Chat");
+    };
+    assert_eq!(4123456, sc.id);
+    assert_eq!(1005, sc.uid);
+    assert_eq!("SC用户", sc.uname);
+    assert_eq!(GuardType::Captain, sc.guard);
+    assert_eq!(30, sc.price);
+    assert_eq!("主播加油", sc.message);
+    assert_eq!(None, sc.message_trans);
+    assert_eq!(60, sc.duration);
+    assert_eq!(1650000000, sc.start_time);
+    assert_eq!("#EDF5FF", sc.background_color);
+    assert_eq!("#2A60B2", sc.background_bottom_color);
+    assert_eq!("#7497CD", sc.background_price_color);
+    assert_eq!("#A3F6FF", sc.message_font_color);
+  }
+
+  #[test]
+  fn test_super_chat_jpn_dropped() {
+    // sent along with SUPER_CHAT_MESSAGE, publishing both thanks twice
+    let notification: NotificationBody =
+      serde_json::from_str(include_str!("fixtures/super_chat_message_jpn.json")).unwrap();
+    assert!(BiliMessage::from_notification(notification).is_none());
+  }
+
+  #[test]
+  fn test_super_chat_delete() {
+    let msg = fixture(include_str!("fixtures/super_chat_message_delete.json"));
+    let BiliMessage::SuperChatDelete { ids } = msg else {
+      panic!("Not a Super Chat Deletion");
+    };
+    assert_eq!(vec![4123456, 4123457], ids);
+  }
+
+  #[test]
+  fn test_guard_buy() {
+    let BiliMessage::GuardPurchase(guard) = fixture(include_str!("fixtures/guard_buy.json")) else {
+      panic!("Not a Guard Purchase");
+    };
+    assert_eq!(1006, guard.uid);
+    assert_eq!("舰长用户", guard.uname);
+    assert_eq!(GuardType::Captain, guard.guard);
+    assert_eq!(1, guard.months);
+    assert_eq!(198, guard.price);
+    assert!(!guard.is_renewal);
+  }
+
+  #[test]
+  fn test_user_toast() {
+    let BiliMessage::GuardPurchase(guard) = fixture(include_str!("fixtures/user_toast_msg.json"))
+    else {
+      panic!("Not a Guard Purchase");
+    };
+    assert_eq!(1006, guard.uid);
+    assert_eq!("舰长用户", guard.uname);
+    assert_eq!(GuardType::Captain, guard.guard);
+    assert_eq!(1, guard.months);
+    assert_eq!(138, guard.price);
+    assert!(guard.is_renewal);
+  }
+
+  fn interaction(msg_type: u64) -> Option<InteractionMessage> {
+    let mut notification: NotificationBody =
+      serde_json::from_str(include_str!("fixtures/interact_word.json")).unwrap();
+    notification["data"]["msg_type"] = msg_type.into();
+    match BiliMessage::from_notification(notification)? {
+      BiliMessage::Interaction(interaction) => Some(interaction),
+      msg => panic!("Not an Interaction: {msg:?}"),
+    }
+  }
+
+  #[test]
+  fn test_interaction() {
+    let enter = interaction(1).unwrap();
+    assert_eq!(1003, enter.uid);
+    assert_eq!("进场用户", enter.uname);
+    assert_eq!(InteractionType::Enter, enter.kind);
+    assert_eq!(GuardType::Captain, enter.guard);
+    assert_eq!(1650000002, enter.timestamp);
+    let medal = enter.medal().unwrap();
+    assert_eq!(10, medal.level);
+    assert_eq!("粉丝团", medal.name);
+    assert_eq!(12345, medal.streamer_roomid);
+
+    let kinds: Vec<InteractionType> = (2..=5).map(|t| interaction(t).unwrap().kind).collect();
+    assert_eq!(
+      vec![
+        InteractionType::Follow,
+        InteractionType::Share,
+        InteractionType::SpecialFollow,
+        InteractionType::MutualFollow
+      ],
+      kinds
+    );
+    assert!(interaction(0).is_none());
+    assert!(interaction(6).is_none());
+  }
+
+  #[test]
+  fn test_interaction_without_medal() {
+    let notification = serde_json::json!({
+      "cmd": "INTERACT_WORD",
+      "data": {
+        "fans_medal": {"medal_level": 0, "medal_name": ""},
+        "msg_type": 2,
+        "uid": 1,
+        "uname": "路人",
+      }
+    });
+    let Some(BiliMessage::Interaction(follow)) = BiliMessage::from_notification(notification)
+    else {
+      panic!("Not an Interaction");
+    };
+    assert!(follow.medal().is_none());
+    assert_eq!(GuardType::NoGuard, follow.guard);
+    assert_eq!(InteractionType::Follow, follow.kind);
+  }
+
+  #[test]
+  fn test_entry_effect() {
+    let BiliMessage::GuardEntry(entry) = fixture(include_str!("fixtures/entry_effect.json")) else {
+      panic!("Not a Guard Entry");
+    };
+    assert_eq!(1006, entry.uid);
+    assert_eq!("舰长用户", entry.uname);
+    assert_eq!(GuardType::Captain, entry.guard);
+    assert_eq!("欢迎舰长 舰长用户 进入直播间", entry.copy_writing);
+    let effect = entry.effect.unwrap();
+    assert_eq!(4, effect.id);
+    assert_eq!(70, effect.priority);
+    assert_eq!("#ffffff", effect.copy_color);
+    assert_eq!("#E6FF00", effect.highlight_color);
+    assert_eq!(
+      "https://i0.hdslb.com/bfs/live/mlive/f34c7441cdbad86f76edebf74e60b59d2958f6ad.png",
+      effect.basemap_url
+    );
+    assert_eq!(2, effect.effective_time);
+
+    // not a guard
+    let mut notification: NotificationBody =
+      serde_json::from_str(include_str!("fixtures/entry_effect.json")).unwrap();
+    notification["data"]["privilege_type"] = 0.into();
+    assert!(BiliMessage::from_notification(notification).is_none());
+  }
+
+  #[test]
+  fn test_welcome_guard() {
+    let notification = serde_json::json!({
+      "cmd": "WELCOME_GUARD",
+      "data": {"uid": 1007, "username": "提督用户", "guard_level": 2}
+    });
+    let Some(BiliMessage::GuardEntry(entry)) = BiliMessage::from_notification(notification) else {
+      panic!("Not a Guard Entry");
+    };
+    assert_eq!(1007, entry.uid);
+    assert_eq!("提督用户", entry.uname);
+    assert_eq!(GuardType::Admiral, entry.guard);
+    assert_eq!("欢迎提督 提督用户 进入直播间", entry.copy_writing);
+    assert!(entry.effect.is_none());
+  }
+
+  #[test]
+  fn test_highlight_markup() {
+    assert_eq!(Some("用户"), highlighted("欢迎 <%用户%> 进入"));
+    assert_eq!(None, highlighted("欢迎 <%用户 进入"));
+    assert_eq!(None, highlighted("欢迎用户进入"));
+    assert_eq!("欢迎 用户 进入", strip_highlight("欢迎 <%用户%> 进入"));
+  }
+
+  #[test]
+  fn test_malformed_super_chat() {
+    let notification = serde_json::json!({"cmd": "SUPER_CHAT_MESSAGE", "data": {"id": 1}});
+    assert!(BiliMessage::from_notification(notification).is_none());
+  }
+
+  #[test]
+  fn test_room_change() {
+    let BiliMessage::RoomEvent(event) = fixture(include_str!("fixtures/room_change.json")) else {
+      panic!("Not a Room Event");
+    };
+    assert_eq!(
+      RoomEvent::RoomChange {
+        title: "今天也要开心地唱歌".to_string(),
+        area_name: "唱见".to_string(),
+        parent_area_name: "娱乐".to_string(),
+      },
+      event
+    );
+  }
+
+  #[test]
+  fn test_live_status_events() {
+    let event = |notification: Value| match BiliMessage::from_notification(notification) {
+      Some(BiliMessage::RoomEvent(event)) => event,
+      other => panic!("Not a Room Event: {:?}", other),
+    };
+    assert_eq!(
+      RoomEvent::Live {
+        live_time: Some(1650000000)
+      },
+      event(serde_json::json!({"cmd": "LIVE", "roomid": 23058, "live_time": 1650000000}))
+    );
+    assert_eq!(
+      RoomEvent::Live { live_time: None },
+      event(serde_json::json!({"cmd": "LIVE", "roomid": 23058}))
+    );
+    assert_eq!(
+      RoomEvent::Preparing,
+      event(serde_json::json!({"cmd": "PREPARING", "roomid": "23058"}))
+    );
+    assert_eq!(
+      RoomEvent::CutOff {
+        msg: "违反直播规范".to_string()
+      },
+      event(serde_json::json!({"cmd": "CUT_OFF", "msg": "违反直播规范", "roomid": 23058}))
+    );
+    assert_eq!(
+      RoomEvent::Warning {
+        msg: "请调整直播内容".to_string()
+      },
+      event(serde_json::json!({"cmd": "WARNING", "msg": "请调整直播内容", "roomid": 23058}))
+    );
+  }
+
+  #[test]
+  fn test_online_rank_top() {
+    let BiliMessage::OnlineRankTop(users) = fixture(include_str!("fixtures/online_rank_v2.json"))
+    else {
+      panic!("Not an Online Rank");
+    };
+    let unames: Vec<&str> = users.iter().map(|user| user.uname.as_str()).collect();
+    assert_eq!(vec!["第一名", "第二名", "第三名"], unames);
+    assert_eq!(1001, users[0].uid);
+    assert_eq!(19800, users[0].score);
+    assert_eq!(GuardType::Captain, users[0].guard);
+    assert_eq!("http://i0.hdslb.com/bfs/face/first.jpg", users[0].face);
+  }
+
+  #[test]
+  fn test_audience_counts() {
+    let count =
+      fixture(r#"{"cmd": "ONLINE_RANK_COUNT", "data": {"count": 1234, "online_count": 2345}}"#);
+    assert!(matches!(
+      count,
+      BiliMessage::OnlineRankCount { count: 1234 }
+    ));
+
+    let BiliMessage::WatchedChange { num, text } = fixture(
+      r#"{"cmd": "WATCHED_CHANGE", "data": {"num": 12345, "text_small": "1.2万", "text_large": "1.2万人看过"}}"#,
+    ) else {
+      panic!("Not a Watched Change");
+    };
+    assert_eq!(12345, num);
+    assert_eq!("1.2万人看过", text);
+
+    let likes = fixture(r#"{"cmd": "LIKE_INFO_V3_UPDATE", "data": {"click_count": 5678}}"#);
+    assert!(matches!(likes, BiliMessage::LikeCount { count: 5678 }));
+
+    let malformed = serde_json::json!({"cmd": "ONLINE_RANK_COUNT", "data": {}});
+    assert!(BiliMessage::from_notification(malformed).is_none());
+  }
+
+  #[test]
+  fn test_unknown_command() {
+    let BiliMessage::Raw { cmd, body } =
+      fixture(r#"{"cmd": "HOT_RANK_CHANGED", "data": {"rank": 12}}"#)
+    else {
+      panic!("Not a Raw Message");
+    };
+    assert_eq!("HOT_RANK_CHANGED", cmd);
+    assert_eq!(Some(12), body.pointer("/data/rank").and_then(Value::as_u64));
+  }
+
+  #[test]
+  fn test_danmu() {
+    let BiliMessage::Danmu(danmu) = fixture(include_str!("fixtures/danmu_msg.json")) else {
+      panic!("Not a Danmu");
+    };
+    assert_eq!(1008, danmu.uid);
+    assert_eq!("表情用户", danmu.uname);
+    assert_eq!("[比心]", danmu.content);
+    assert!(danmu.is_manager);
+    assert_eq!(5, danmu.mode);
+    assert_eq!("#E33FFF", danmu.color);
+    assert_eq!(
+      Some("a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6"),
+      danmu.id_str.as_deref()
+    );
+    assert_eq!(Some("弹幕用户"), danmu.reply_uname.as_deref());
+    assert_eq!(
+      Some(Emoticon {
+        unique: "upower_[主播_比心]".to_string(),
+        url: "http://i0.hdslb.com/bfs/live/heart.png".to_string(),
+        width: 162,
+        height: 162,
+      }),
+      danmu.emoticon
+    );
+    assert_eq!(GuardType::Captain, danmu.guard);
+
+    let medal = danmu.medal.unwrap();
+    assert_eq!(21, medal.level);
+    assert_eq!("粉丝团", medal.name);
+    assert_eq!("#1A544B", medal.color);
+    assert_eq!("#67E8FF", medal.color_border);
+    assert_eq!("#1A544B", medal.color_start);
+    assert_eq!("#529D92", medal.color_end);
+    assert_eq!(GuardType::Captain, medal.guard);
+    assert!(medal.is_lighted);
+  }
+
+  #[test]
+  fn test_plain_danmu() {
+    let notification: Value = serde_json::from_str(LIVE_SESSION).unwrap();
+    let BiliMessage::Danmu(danmu) =
+      BiliMessage::from_notification(notification[0].clone()).unwrap()
+    else {
+      panic!("Not a Danmu");
+    };
+    assert_eq!("晚上好", danmu.content);
+    assert_eq!(1, danmu.mode);
+    assert_eq!("#FFFFFF", danmu.color);
+    // the extra JSON is empty
+    assert_eq!(None, danmu.id_str);
+    assert_eq!(None, danmu.reply_uname);
+    assert_eq!(None, danmu.emoticon);
+    assert_eq!("#5C968E", danmu.medal.unwrap().color);
+  }
+
+  #[test]
+  fn test_truncated_danmu() {
+    // missing the sender
+    let notification = serde_json::json!({
+      "cmd": "DANMU_MSG",
+      "info": [[0, 1, 25, 16777215, 1650000000000u64, 0, 0, "", 0, 0], "晚上好"]
+    });
+    assert!(BiliMessage::from_notification(notification).is_none());
+
+    // missing everything after the sender
+    let notification = serde_json::json!({
+      "cmd": "DANMU_MSG",
+      "info": [[0, 1, 25, 16777215, 1650000000000u64, 0, 0, "", 0, 0], "晚上好", [1001]]
+    });
+    let BiliMessage::Danmu(danmu) = BiliMessage::from_notification(notification).unwrap() else {
+      panic!("Not a Danmu");
+    };
+    assert_eq!(1001, danmu.uid);
+    assert_eq!("B站用户", danmu.uname);
+    assert!(danmu.medal.is_none());
+    assert_eq!(GuardType::NoGuard, danmu.guard);
+  }
+
+  #[test]
+  fn test_blind_gift() {
+    let BiliMessage::Gift(gift) = fixture(include_str!("fixtures/send_gift.json")) else {
+      panic!("Not a Gift");
+    };
+    assert_eq!(1009, gift.uid);
+    assert_eq!(GuardType::Admiral, gift.guard);
+    // combo_send is null
+    assert_eq!(32269, gift.gift_id);
+    assert_eq!("浪漫城堡", gift.gift_name);
+    assert_eq!(2, gift.gift_num);
+    assert_eq!(160000, gift.price);
+    assert_eq!(CoinType::Gold, gift.coin_type);
+    assert_eq!(300000, gift.total_coin);
+    assert_eq!(Some("1650000004110200002"), gift.tid.as_deref());
+    assert_eq!(
+      Some("batch:gift:combo_id:1009:67890:32269:1650000004.0001"),
+      gift.batch_combo_id.as_deref()
+    );
+    assert_eq!(
+      Some(BlindGift {
+        original_gift_id: 32251,
+        original_gift_name: "心动盲盒".to_string(),
+        original_gift_price: 150000,
+      }),
+      gift.blind_gift
+    );
+    assert_eq!(
+      Some(GiftReceiver {
+        uid: 67890,
+        uname: "主播".to_string(),
+      }),
+      gift.receiver
+    );
+    // what was paid for the boxes, not what they are worth
+    assert_eq!(300.0, gift.total_value_in_cny());
+    let medal = gift.medal().unwrap();
+    assert_eq!(25, medal.level);
+    assert_eq!("粉丝团", medal.name);
+    assert_eq!(12345, medal.streamer_roomid);
+  }
+
+  #[test]
+  fn test_combo_gift() {
+    let BiliMessage::Gift(gift) = fixture(include_str!("fixtures/combo_send.json")) else {
+      panic!("Not a Gift");
+    };
+    assert_eq!("小花花", gift.gift_name);
+    assert_eq!(3, gift.gift_num);
+    assert_eq!(100, gift.price);
+    assert_eq!(300, gift.total_coin);
+    assert!(gift.blind_gift.is_none());
+    // medal level 0 means no medal worn
+    assert!(gift.medal().is_none());
+    assert_eq!(
+      Some("主播"),
+      gift.receiver.as_ref().map(|r| r.uname.as_str())
+    );
+    assert_eq!(0.3, gift.total_value_in_cny());
+  }
+
+  #[test]
+  fn test_silver_gift() {
+    let notification = serde_json::json!({
+      "cmd": "SEND_GIFT",
+      "data": {
+        "uid": 1010, "uname": "白嫖用户", "guard_level": 0, "giftId": 1,
+        "giftName": "辣条", "num": 5, "price": 100, "coin_type": "silver",
+        "total_coin": 500, "batch_combo_id": "", "blind_gift": null
+      }
+    });
+    let BiliMessage::Gift(gift) = BiliMessage::from_notification(notification).unwrap() else {
+      panic!("Not a Gift");
+    };
+    assert_eq!(CoinType::Silver, gift.coin_type);
+    assert_eq!(500, gift.total_coin);
+    assert_eq!(None, gift.batch_combo_id);
+    assert!(gift.blind_gift.is_none());
+    assert!(gift.receiver.is_none());
+    assert_eq!(0.0, gift.total_value_in_cny());
+  }
+
+  #[test]
+  fn test_red_pocket() {
+    let BiliMessage::RedPocketStart(red_pocket) =
+      fixture(include_str!("fixtures/popularity_red_pocket_start.json"))
+    else {
+      panic!("Not a Red Pocket");
+    };
+    assert_eq!(7654321, red_pocket.lot_id);
+    assert_eq!(1011, red_pocket.sender_uid);
+    assert_eq!("红包用户", red_pocket.sender_name);
+    assert_eq!("老板大气！点点红包抽礼物", red_pocket.danmu);
+    assert_eq!(1650000190, red_pocket.end_time);
+    let awards: Vec<(&str, u64)> = red_pocket
+      .awards
+      .iter()
+      .map(|award| (award.gift_name.as_str(), award.num))
+      .collect();
+    assert_eq!(vec![("打call", 2), ("牛哇", 3)], awards);
+
+    let BiliMessage::RedPocketWinners { lot_id, winners } = fixture(include_str!(
+      "fixtures/popularity_red_pocket_winner_list.json"
+    )) else {
+      panic!("Not Red Pocket Winners");
+    };
+    assert_eq!(7654321, lot_id);
+    let winners: Vec<(u64, &str, &str)> = winners
+      .iter()
+      .map(|w| (w.uid, w.uname.as_str(), w.award_name.as_str()))
+      .collect();
+    assert_eq!(
+      vec![(1012, "中奖用户", "打call"), (1013, "幸运用户", "牛哇")],
+      winners
+    );
+  }
+
+  #[test]
+  fn test_anchor_lottery() {
+    let BiliMessage::AnchorLotteryStart(lottery) =
+      fixture(include_str!("fixtures/anchor_lot_start.json"))
+    else {
+      panic!("Not a Lottery");
+    };
+    assert_eq!(2345678, lottery.id);
+    assert_eq!("5元红包", lottery.award_name);
+    assert_eq!(1, lottery.award_num);
+    assert_eq!("关注主播，参与天选", lottery.danmu);
+    assert_eq!("关注主播", lottery.require_text);
+    assert_eq!(Some("小花花"), lottery.gift_name.as_deref());
+    assert_eq!(600, lottery.max_time);
+
+    let end = fixture(r#"{"cmd": "ANCHOR_LOT_END", "data": {"id": 2345678}}"#);
+    assert!(matches!(end, BiliMessage::AnchorLotteryEnd { id: 2345678 }));
+
+    let BiliMessage::AnchorLotteryAward {
+      id,
+      award_name,
+      winners,
+    } = fixture(include_str!("fixtures/anchor_lot_award.json"))
+    else {
+      panic!("Not a Lottery Award");
+    };
+    assert_eq!(2345678, id);
+    assert_eq!("5元红包", award_name);
+    assert_eq!(
+      vec![LotteryWinner {
+        uid: 1014,
+        uname: "天选用户".to_string(),
+        award_name: "5元红包".to_string(),
+      }],
+      winners
+    );
+  }
+
+  fn pk_event(json: &str) -> PkEvent {
+    let BiliMessage::Pk(event) = fixture(json) else {
+      panic!("Not a PK Event");
+    };
+    event
+  }
+
+  #[test]
+  fn test_pk_events() {
+    assert_eq!(
+      PkEvent::Pre {
+        pk_id: 300123,
+        opponent: PkOpponent {
+          room_id: 54321,
+          uid: 99999,
+          uname: "对手主播".to_string(),
+        },
+      },
+      pk_event(include_str!("fixtures/pk_battle_pre_new.json"))
+    );
+    assert_eq!(
+      PkEvent::Start {
+        pk_id: 300123,
+        init_room_id: 54321,
+        matched_room_id: 12345,
+        start_time: 1650000100,
+        end_time: 1650000410,
+      },
+      pk_event(include_str!("fixtures/pk_battle_start_new.json"))
+    );
+    let PkEvent::Progress { init, matched, .. } =
+      pk_event(include_str!("fixtures/pk_battle_process_new.json"))
+    else {
+      panic!("Not a PK Progress");
+    };
+    assert_eq!((54321, 50), (init.room_id, init.votes));
+    assert_eq!((12345, 100), (matched.room_id, matched.votes));
+    assert_eq!("送礼用户", matched.best_uname);
+
+    // pk_id is a string here
+    let PkEvent::End {
+      pk_id,
+      winner_room_id,
+      ..
+    } = pk_event(include_str!("fixtures/pk_battle_end.json"))
+    else {
+      panic!("Not a PK End");
+    };
+    assert_eq!(300123, pk_id);
+    assert_eq!(Some(12345), winner_room_id);
+  }
+
+  #[test]
+  fn test_pk_session() {
+    let mut session = None;
+    for json in [
+      include_str!("fixtures/pk_battle_pre_new.json"),
+      include_str!("fixtures/pk_battle_start_new.json"),
+      include_str!("fixtures/pk_battle_process_new.json"),
+    ] {
+      PkSession::update(&mut session, 12345, &pk_event(json));
+    }
+    // we're the matched room
+    assert_eq!(
+      Some(PkSession {
+        pk_id: 300123,
+        opponent_room_id: 54321,
+        opponent_uname: "对手主播".to_string(),
+        score: 100,
+        opponent_score: 50,
+        end_time: 1650000410,
+        result: None,
+      }),
+      session
+    );
+
+    let end = pk_event(include_str!("fixtures/pk_battle_end.json"));
+    PkSession::update(&mut session, 12345, &end);
+    let ended = session.clone().unwrap();
+    assert_eq!((120, 80), (ended.score, ended.opponent_score));
+    assert_eq!(Some(PkResult::Win), ended.result);
+
+    // seen from the other side
+    let mut other = None;
+    PkSession::update(&mut other, 54321, &end);
+    let other = other.unwrap();
+    assert_eq!(12345, other.opponent_room_id);
+    assert_eq!("", other.opponent_uname);
+    assert_eq!(Some(PkResult::Lose), other.result);
+
+    // a new PK replaces the old one
+    let next = PkEvent::Pre {
+      pk_id: 300124,
+      opponent: PkOpponent {
+        room_id: 11111,
+        uid: 1,
+        uname: "下一个对手".to_string(),
+      },
+    };
+    PkSession::update(&mut session, 12345, &next);
+    let session = session.unwrap();
+    assert_eq!(300124, session.pk_id);
+    assert_eq!(0, session.score);
+    assert_eq!(None, session.result);
+  }
 }
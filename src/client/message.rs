@@ -4,12 +4,12 @@
 //! https://github.com/lovelyyoshino/Bilibili-Live-API/blob/master/API.WebSocket.md
 use std::io::{Cursor, Read};
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
 use flate2::read::ZlibDecoder;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use crate::DanmujiResult;
+use crate::{error::DanmujiError, DanmujiResult};
 
 /// Struct representing BiliBili's top-level websocket message frame.
 /// An entire frame is parsed into a [BiliWebsocketMessage], which can be
@@ -28,7 +28,7 @@ pub struct BiliWebsocketMessage {
 
 impl BiliWebsocketMessage {
   // construct from raw data
-  fn new(data: Vec<u8>, op: OpType, protocol_version: u16) -> Self {
+  pub(super) fn new(data: Vec<u8>, op: OpType, protocol_version: u16) -> Self {
     let packet_length = HEADER_LENGTH as u32 + data.len() as u32;
     Self {
       header: BiliWebsocketHeader::new(packet_length, protocol_version, op),
@@ -36,14 +36,28 @@ impl BiliWebsocketMessage {
     }
   }
 
-  // construct entry security message
-  pub fn entry(room_id: i64, uid: Option<u64>) -> Self {
-    let data = FirstSecurityData::new(room_id, uid);
+  // construct entry security message, `protover` tells the server
+  // how to compress the notifications it sends, and `key` is the auth
+  // token fetched along with the server list
+  pub fn entry(room_id: i64, uid: Option<u64>, protover: u16, key: &str) -> Self {
+    let data = FirstSecurityData::new(room_id, uid, protover, key);
     let data_bytes = serde_json::to_vec(&data).unwrap();
 
     Self::new(data_bytes, OpType::Entry, 2)
   }
 
+  // operation of the frame
+  #[cfg(test)]
+  pub(super) fn op(&self) -> OpType {
+    self.header.op
+  }
+
+  // raw data of the frame
+  #[cfg(test)]
+  pub(super) fn data(&self) -> &[u8] {
+    &self.data
+  }
+
   // construct heartbeat message
   pub fn heartbeat() -> Self {
     // heartbeat message has not data
@@ -55,8 +69,13 @@ impl BiliWebsocketMessage {
   // construct from binary (received from websocket server)
   pub fn from_binary(mut buf: Vec<u8>) -> DanmujiResult<Self> {
     // parse header
-    let (header, _) = buf.split_at(HEADER_LENGTH as usize);
-    let header = BiliWebsocketHeader::from_vec(header);
+    let header = BiliWebsocketHeader::from_vec(&buf)?;
+    if header.packet_length as usize != buf.len() {
+      return Err(DanmujiError::InvalidPacketLength {
+        packet_length: header.packet_length,
+        buffer_length: buf.len(),
+      });
+    }
 
     // the rest is data
     buf.drain(0..HEADER_LENGTH as usize);
@@ -72,92 +91,143 @@ impl BiliWebsocketMessage {
     buf
   }
 
-  /// Consume the message and unpack all the inner messages
-  // todo: This method should ideally return a vector of Result<BiliWebsocketInner>
-  // todo: to propagate parsing error upward, so that we can get rid of the unwraps
-  pub fn parse(self) -> Vec<BiliWebsocketInner> {
+  /// Consume the message and unpack all the inner messages.
+  /// A malformed packet yields an error in place of its inner messages, and
+  /// when it is impossible to tell where the next packet starts, unpacking stops there.
+  pub fn parse(self) -> Vec<DanmujiResult<BiliWebsocketInner>> {
     let BiliWebsocketMessage { header, data } = self;
-    match header.op {
-      OpType::Notification => {
-        match header.protocol_version {
-          // data is zlib compressed
-          2 => {
-            let mut z = ZlibDecoder::new(&data[..]);
-            let mut decompressed_buf = vec![];
-            z.read_to_end(&mut decompressed_buf).unwrap();
-            process_zlib_data(decompressed_buf)
-          }
-          // data is not compressed
-          _ => {
-            vec![BiliWebsocketInner {
+    let mut inners = vec![];
+    unpack(header, &data[..], &mut inners);
+    inners
+  }
+}
+
+// unpack a single packet into inner messages. Compressed notification packets
+// contain one or more packets that could themselves be compressed, so they
+// are unpacked recursively
+fn unpack(
+  header: BiliWebsocketHeader,
+  data: &[u8],
+  inners: &mut Vec<DanmujiResult<BiliWebsocketInner>>,
+) {
+  match header.op {
+    OpType::Notification => {
+      match header.protocol_version {
+        // data is zlib compressed
+        PROTO_ZLIB => match decompress(ZlibDecoder::new(data)) {
+          Ok(buf) => process_packed_data(&buf, inners),
+          Err(err) => inners.push(Err(err)),
+        },
+        // data is brotli compressed
+        PROTO_BROTLI => match decompress(brotli::Decompressor::new(data, BROTLI_BUFFER_SIZE)) {
+          Ok(buf) => process_packed_data(&buf, inners),
+          Err(err) => inners.push(Err(err)),
+        },
+        // data is not compressed
+        _ => {
+          let inner = serde_json::from_slice(data)
+            .map(|body| BiliWebsocketInner {
               _header: header,
-              body: BiliWebsocketMessageBody::Notification(
-                serde_json::from_slice(&data[..]).unwrap(),
-              ),
-            }]
-          }
+              body: BiliWebsocketMessageBody::Notification(body),
+            })
+            .map_err(DanmujiError::from);
+          inners.push(inner);
         }
       }
-      OpType::EntryReply => {
-        vec![BiliWebsocketInner {
-          _header: header,
-          body: BiliWebsocketMessageBody::EntryReply,
-        }]
-      }
+    }
+    OpType::EntryReply => {
+      inners.push(Ok(BiliWebsocketInner {
+        _header: header,
+        body: BiliWebsocketMessageBody::EntryReply,
+      }));
+    }
 
-      OpType::HeartBeatReply => {
-        let mut cursor = Cursor::new(data);
-        let popularity = cursor.read_i32::<BigEndian>().unwrap_or(0);
-        vec![BiliWebsocketInner {
-          _header: header,
-          body: BiliWebsocketMessageBody::RoomPopularity(popularity),
-        }]
-      }
+    OpType::HeartBeatReply => {
+      let mut cursor = Cursor::new(data);
+      let popularity = cursor.read_i32::<BigEndian>().unwrap_or(0);
+      inners.push(Ok(BiliWebsocketInner {
+        _header: header,
+        body: BiliWebsocketMessageBody::RoomPopularity(popularity),
+      }));
+    }
 
-      _ => {
-        // we currently don't deal with client-sent messages
-        // but this could be useful if we'are gonna implement something
-        // lika a mock BiliWebsocket Server
-        warn!("Unexpected Op Type");
-        vec![]
-      }
+    _ => {
+      // we currently don't deal with client-sent messages
+      // but this could be useful if we'are gonna implement something
+      // lika a mock BiliWebsocket Server
+      warn!("Unexpected Op Type: {:?}", header.op);
     }
   }
 }
 
-// decompressed buffer contains one or more
-// messages, we will extract them one by one
-fn process_zlib_data(buf: Vec<u8>) -> Vec<BiliWebsocketInner> {
-  let mut inners = vec![];
-
-  let mut cur_buf = &buf[..];
-
-  let mut offset = 0;
-  let max_length = buf.len();
+// read the decompressed content out of a decoder, failing beyond
+// MAX_DECOMPRESSED_SIZE rather than inflating a malicious packet
+fn decompress(decoder: impl Read) -> DanmujiResult<Vec<u8>> {
+  let mut decompressed_buf = vec![];
+  decoder
+    .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+    .read_to_end(&mut decompressed_buf)
+    .map_err(DanmujiError::Decompression)?;
+  if decompressed_buf.len() > MAX_DECOMPRESSED_SIZE {
+    return Err(DanmujiError::Decompression(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!(
+        "Decompressed Packet Exceeds {} Bytes",
+        MAX_DECOMPRESSED_SIZE
+      ),
+    )));
+  }
+  Ok(decompressed_buf)
+}
 
-  while offset < max_length {
-    // first parse current header
-    let header = BiliWebsocketHeader::from_vec(cur_buf);
-    let packet_length = header.packet_length;
+// decompressed buffer contains one or more
+// packets, we will extract them one by one
+fn process_packed_data(buf: &[u8], inners: &mut Vec<DanmujiResult<BiliWebsocketInner>>) {
+  let mut cur_buf = buf;
+
+  while !cur_buf.is_empty() {
+    if cur_buf.len() < HEADER_LENGTH as usize {
+      inners.push(Err(DanmujiError::TruncatedHeader(cur_buf.len())));
+      return;
+    }
+    // the length is read on its own, so that a packet with a header we
+    // can't parse, e.g. of an unknown op, can still be skipped
+    let packet_length = BigEndian::read_u32(cur_buf);
+    if packet_length < HEADER_LENGTH as u32 || packet_length as usize > cur_buf.len() {
+      // we don't know where the next packet starts, give up the rest
+      inners.push(Err(DanmujiError::InvalidPacketLength {
+        packet_length,
+        buffer_length: cur_buf.len(),
+      }));
+      return;
+    }
 
-    // this_buf: buffer for current inner
+    // this_buf: buffer for current packet
     // next_buf: the rest
     let (this_buf, next_buf) = cur_buf.split_at(packet_length as usize);
-
-    // todo: get rid of this unwrap by returning a Result
-    let this_inner = BiliWebsocketInner::from_binary(this_buf).unwrap();
-    inners.push(this_inner);
+    match BiliWebsocketHeader::from_vec(this_buf) {
+      Ok(header) => unpack(header, &this_buf[HEADER_LENGTH as usize..], inners),
+      Err(err) => inners.push(Err(err)),
+    }
 
     cur_buf = next_buf;
-
-    offset += packet_length as usize;
   }
-
-  inners
 }
 
 // header length is fixed 16
 const HEADER_LENGTH: u16 = 16;
+// protocol versions:
+// 0: uncompressed json notification
+// 1: heartbeat & entry packets (body is not json)
+// 2: zlib compressed packets
+// 3: brotli compressed packets
+pub const PROTO_ZLIB: u16 = 2;
+pub const PROTO_BROTLI: u16 = 3;
+// buffer size used by the brotli decompressor
+pub(super) const BROTLI_BUFFER_SIZE: usize = 4096;
+// a compressed packet decompresses to at most this many bytes,
+// real ones are a few KBs
+const MAX_DECOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
 // don't know what's for, just 1
 const SEQ: u32 = 1;
 // Header Format:
@@ -188,25 +258,28 @@ impl BiliWebsocketHeader {
   }
 
   /// read and parse a [BiliWebsocketHeader] from given byte array
-  fn from_vec(buf: &[u8]) -> Self {
+  fn from_vec(buf: &[u8]) -> DanmujiResult<Self> {
     // sanity check
-    assert!(HEADER_LENGTH as usize <= buf.len());
+    if buf.len() < HEADER_LENGTH as usize {
+      return Err(DanmujiError::TruncatedHeader(buf.len()));
+    }
 
     let mut cursor = Cursor::new(buf);
 
-    let packet_length = cursor.read_u32::<BigEndian>().unwrap();
-    let header_length = cursor.read_u16::<BigEndian>().unwrap();
-    let protocol_version = cursor.read_u16::<BigEndian>().unwrap();
-    let op: OpType = cursor.read_u32::<BigEndian>().unwrap().into();
-    let seq = cursor.read_u32::<BigEndian>().unwrap();
+    // buffer is long enough, reads are infallible from here
+    let packet_length = cursor.read_u32::<BigEndian>()?;
+    let header_length = cursor.read_u16::<BigEndian>()?;
+    let protocol_version = cursor.read_u16::<BigEndian>()?;
+    let op = OpType::try_from(cursor.read_u32::<BigEndian>()?)?;
+    let seq = cursor.read_u32::<BigEndian>()?;
 
-    Self {
+    Ok(Self {
       packet_length,
       header_length,
       protocol_version,
       op,
       seq,
-    }
+    })
   }
 
   fn to_vec(&self) -> Vec<u8> {
@@ -238,21 +311,20 @@ pub enum OpType {
   Notification = 5,
   Entry = 7,
   EntryReply = 8,
-
-  // place holder for all unknown ops
-  Unknown = 0,
 }
 
-impl From<u32> for OpType {
-  fn from(value: u32) -> Self {
+impl TryFrom<u32> for OpType {
+  type Error = DanmujiError;
+
+  fn try_from(value: u32) -> DanmujiResult<Self> {
     match value {
-      2 => OpType::HeartBeat,
-      3 => OpType::HeartBeatReply,
-      5 => OpType::Notification,
-      7 => OpType::Entry,
-      8 => OpType::EntryReply,
+      2 => Ok(OpType::HeartBeat),
+      3 => Ok(OpType::HeartBeatReply),
+      5 => Ok(OpType::Notification),
+      7 => Ok(OpType::Entry),
+      8 => Ok(OpType::EntryReply),
 
-      _ => OpType::Unknown,
+      _ => Err(DanmujiError::UnknownOp(value)),
     }
   }
 }
@@ -268,18 +340,20 @@ struct FirstSecurityData {
   roomid: i64,
   #[serde(rename = "type")]
   type_: u64,
+  key: String,
 }
 
 impl FirstSecurityData {
-  fn new(roomid: i64, uid: Option<u64>) -> Self {
+  fn new(roomid: i64, uid: Option<u64>, protover: u16, key: &str) -> Self {
     let uid = uid.unwrap_or(0);
     Self {
       clientver: "1.14.0",
       platform: "web",
-      protover: 1,
+      protover: protover as u64,
       uid,
       roomid,
       type_: 2,
+      key: key.to_string(),
     }
   }
 }
@@ -305,6 +379,11 @@ impl BiliWebsocketInner {
   pub fn into_body(self) -> BiliWebsocketMessageBody {
     self.body
   }
+
+  /// Borrow the body of the message
+  pub fn body(&self) -> &BiliWebsocketMessageBody {
+    &self.body
+  }
 }
 
 /// Message Body of a Bilibili's websocket message,
@@ -332,58 +411,15 @@ pub enum BiliWebsocketMessageBody {
 /// (Danmu, Gift, Subscription, etc).
 pub type NotificationBody = serde_json::Value;
 
-impl BiliWebsocketInner {
-  /// parse a [BiliWebsocketInner] from raw binary data
-  fn from_binary(buf: &[u8]) -> DanmujiResult<Self> {
-    // sanity check
-    assert!(HEADER_LENGTH as usize <= buf.len());
-
-    let (header, content) = buf.split_at(HEADER_LENGTH as usize);
-
-    let header = BiliWebsocketHeader::from_vec(header);
-
-    let body = match header.op {
-      OpType::Notification => {
-        let content_buf = if header.protocol_version == 2 {
-          // zlib
-          let mut z = ZlibDecoder::new(Cursor::new(content));
-          let mut decompressed_buf = vec![];
-          z.read_to_end(&mut decompressed_buf).unwrap();
-          decompressed_buf
-        } else {
-          content.to_vec()
-        };
-
-        BiliWebsocketMessageBody::Notification(serde_json::from_slice(&content_buf[..]).unwrap())
-      }
-
-      OpType::EntryReply => BiliWebsocketMessageBody::EntryReply,
-
-      OpType::HeartBeatReply => {
-        let mut cursor = Cursor::new(content);
-        let popularity = cursor.read_i32::<BigEndian>().unwrap_or(0);
-
-        BiliWebsocketMessageBody::RoomPopularity(popularity)
-      }
-
-      // currently don't deal with client-sent op types
-      _ => unimplemented!(),
-    };
-
-    Ok(Self {
-      _header: header,
-      body,
-    })
-  }
-}
-
 #[cfg(test)]
 mod tests {
   use std::io::Cursor;
 
   use byteorder::ReadBytesExt;
+  use rand::Rng;
 
   use super::*;
+  use crate::client::mock::{brotli_compress, zlib_compress};
 
   #[derive(Serialize, Deserialize, PartialEq, Eq)]
   struct TestJsonData {
@@ -419,7 +455,7 @@ mod tests {
     // read operation type
     assert_eq!(
       OpType::Entry,
-      cursor.read_u32::<BigEndian>().unwrap().into()
+      cursor.read_u32::<BigEndian>().unwrap().try_into().unwrap()
     );
     // read seq
     assert_eq!(SEQ, cursor.read_u32::<BigEndian>().unwrap());
@@ -448,4 +484,247 @@ mod tests {
 
     assert_eq!(msg, recovered_msg);
   }
+
+  // build a notification packet carrying the given json body
+  fn notification(body: &serde_json::Value) -> Vec<u8> {
+    BiliWebsocketMessage::new(serde_json::to_vec(body).unwrap(), OpType::Notification, 0).to_vec()
+  }
+
+  fn notification_bodies(inners: Vec<DanmujiResult<BiliWebsocketInner>>) -> Vec<serde_json::Value> {
+    inners
+      .into_iter()
+      .map(|inner| match inner.unwrap().into_body() {
+        BiliWebsocketMessageBody::Notification(body) => body,
+        body => panic!("Unexpected Body: {body:?}"),
+      })
+      .collect()
+  }
+
+  fn fixtures() -> Vec<serde_json::Value> {
+    vec![
+      serde_json::json!({"cmd": "DANMU_MSG", "info": [[0], "你好", [1, "测试用户"]]}),
+      serde_json::json!({"cmd": "SEND_GIFT", "data": {"uid": 1, "giftName": "小花花"}}),
+      serde_json::json!({"cmd": "INTERACT_WORD", "data": {"uid": 2}}),
+    ]
+  }
+
+  #[test]
+  fn test_entry_protover() {
+    let entry = BiliWebsocketMessage::entry(1, Some(2), PROTO_BROTLI, "token");
+    let data: serde_json::Value = serde_json::from_slice(&entry.data).unwrap();
+    assert_eq!(PROTO_BROTLI as u64, data["protover"].as_u64().unwrap());
+    assert_eq!(1, data["roomid"].as_i64().unwrap());
+    assert_eq!(2, data["uid"].as_u64().unwrap());
+    assert_eq!("token", data["key"].as_str().unwrap());
+  }
+
+  #[test]
+  fn test_zlib_round_trip() {
+    let packed: Vec<u8> = fixtures().iter().flat_map(notification).collect();
+    let msg = BiliWebsocketMessage::new(zlib_compress(&packed), OpType::Notification, PROTO_ZLIB);
+
+    let recovered = BiliWebsocketMessage::from_binary(msg.to_vec()).unwrap();
+    assert_eq!(fixtures(), notification_bodies(recovered.parse()));
+  }
+
+  #[test]
+  fn test_brotli_round_trip() {
+    let packed: Vec<u8> = fixtures().iter().flat_map(notification).collect();
+    let msg =
+      BiliWebsocketMessage::new(brotli_compress(&packed), OpType::Notification, PROTO_BROTLI);
+
+    let recovered = BiliWebsocketMessage::from_binary(msg.to_vec()).unwrap();
+    assert_eq!(fixtures(), notification_bodies(recovered.parse()));
+  }
+
+  #[test]
+  fn test_nested_round_trip() {
+    let fixtures = fixtures();
+    // a zlib packet carrying the first two notifications, batched
+    // with the last notification in a brotli frame
+    let zlib_packed: Vec<u8> = fixtures[..2].iter().flat_map(notification).collect();
+    let zlib_packet = BiliWebsocketMessage::new(
+      zlib_compress(&zlib_packed),
+      OpType::Notification,
+      PROTO_ZLIB,
+    );
+    let mut packed = zlib_packet.to_vec();
+    packed.extend(notification(&fixtures[2]));
+    let msg =
+      BiliWebsocketMessage::new(brotli_compress(&packed), OpType::Notification, PROTO_BROTLI);
+
+    let recovered = BiliWebsocketMessage::from_binary(msg.to_vec()).unwrap();
+    assert_eq!(fixtures, notification_bodies(recovered.parse()));
+  }
+
+  #[test]
+  fn test_truncated_header() {
+    let buf = BiliWebsocketMessage::heartbeat().to_vec();
+    let res = BiliWebsocketMessage::from_binary(buf[..HEADER_LENGTH as usize - 1].to_vec());
+    assert!(matches!(res, Err(DanmujiError::TruncatedHeader(15))));
+  }
+
+  #[test]
+  fn test_invalid_packet_length() {
+    let mut buf = BiliWebsocketMessage::entry(1, None, PROTO_ZLIB, "").to_vec();
+    buf.pop();
+    let res = BiliWebsocketMessage::from_binary(buf);
+    assert!(matches!(res, Err(DanmujiError::InvalidPacketLength { .. })));
+
+    // a packed packet that claims more bytes than there are
+    let mut packet = notification(&fixtures()[0]);
+    packet.truncate(packet.len() - 1);
+    let msg = BiliWebsocketMessage::new(zlib_compress(&packet), OpType::Notification, PROTO_ZLIB);
+    let inners = msg.parse();
+    assert_eq!(1, inners.len());
+    assert!(matches!(
+      inners[0],
+      Err(DanmujiError::InvalidPacketLength { .. })
+    ));
+  }
+
+  #[test]
+  fn test_unknown_op() {
+    let mut buf = BiliWebsocketMessage::heartbeat().to_vec();
+    // overwrite op with an unknown value
+    buf[8..12].copy_from_slice(&42u32.to_be_bytes());
+    let res = BiliWebsocketMessage::from_binary(buf.clone());
+    assert!(matches!(res, Err(DanmujiError::UnknownOp(42))));
+
+    // a packed packet of an unknown op is skipped, the rest is kept
+    let fixtures = fixtures();
+    let mut packed = notification(&fixtures[0]);
+    packed.extend(buf);
+    packed.extend(notification(&fixtures[1]));
+    let msg = BiliWebsocketMessage::new(zlib_compress(&packed), OpType::Notification, PROTO_ZLIB);
+    let inners = msg.parse();
+    assert_eq!(3, inners.len());
+    assert!(inners[0].is_ok());
+    assert!(matches!(inners[1], Err(DanmujiError::UnknownOp(42))));
+    assert!(inners[2].is_ok());
+  }
+
+  #[test]
+  fn test_decompression_error() {
+    for protover in [PROTO_ZLIB, PROTO_BROTLI] {
+      let msg = BiliWebsocketMessage::new(
+        b"definitely not compressed".to_vec(),
+        OpType::Notification,
+        protover,
+      );
+      let inners = msg.parse();
+      assert_eq!(1, inners.len());
+      assert!(matches!(inners[0], Err(DanmujiError::Decompression(_))));
+    }
+  }
+
+  #[test]
+  fn test_decompression_bomb() {
+    let bomb = vec![0; MAX_DECOMPRESSED_SIZE + 1];
+    for msg in [
+      BiliWebsocketMessage::new(zlib_compress(&bomb), OpType::Notification, PROTO_ZLIB),
+      BiliWebsocketMessage::new(brotli_compress(&bomb), OpType::Notification, PROTO_BROTLI),
+    ] {
+      let inners = msg.parse();
+      assert_eq!(1, inners.len());
+      assert!(matches!(inners[0], Err(DanmujiError::Decompression(_))));
+    }
+  }
+
+  #[test]
+  fn test_malformed_packet_is_skipped() {
+    let fixtures = fixtures();
+    let mut packed = notification(&fixtures[0]);
+    packed
+      .extend(BiliWebsocketMessage::new(b"{not json".to_vec(), OpType::Notification, 0).to_vec());
+    packed.extend(notification(&fixtures[1]));
+    let msg = BiliWebsocketMessage::new(zlib_compress(&packed), OpType::Notification, PROTO_ZLIB);
+
+    let inners = msg.parse();
+    assert_eq!(3, inners.len());
+    assert!(inners[0].is_ok());
+    assert!(matches!(inners[1], Err(DanmujiError::JsonError(_))));
+    assert!(inners[2].is_ok());
+  }
+
+  // parse whatever comes out of a frame, asserting only that nothing panics
+  fn parse_random(buf: Vec<u8>) {
+    if let Ok(msg) = BiliWebsocketMessage::from_binary(buf) {
+      let _ = msg.parse();
+    }
+  }
+
+  #[test]
+  fn test_fuzz_random_bytes() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..10000 {
+      let len = rng.gen_range(0..256);
+      let buf: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+      parse_random(buf);
+    }
+  }
+
+  #[test]
+  fn test_fuzz_random_bodies() {
+    // well-formed headers with random versions & bodies
+    let mut rng = rand::thread_rng();
+    let ops = [
+      OpType::HeartBeatReply,
+      OpType::Notification,
+      OpType::EntryReply,
+    ];
+    for _ in 0..10000 {
+      let len = rng.gen_range(0..256);
+      let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+      let op = ops[rng.gen_range(0..ops.len())];
+      let msg = BiliWebsocketMessage::new(data, op, rng.gen_range(0..4));
+      parse_random(msg.to_vec());
+    }
+  }
+
+  #[test]
+  fn test_fuzz_corrupted_frames() {
+    let packed: Vec<u8> = fixtures().iter().flat_map(notification).collect();
+    let frames = [
+      BiliWebsocketMessage::new(zlib_compress(&packed), OpType::Notification, PROTO_ZLIB),
+      BiliWebsocketMessage::new(brotli_compress(&packed), OpType::Notification, PROTO_BROTLI),
+      // a single uncompressed notification
+      BiliWebsocketMessage::new(
+        serde_json::to_vec(&fixtures()[0]).unwrap(),
+        OpType::Notification,
+        0,
+      ),
+    ];
+
+    let mut rng = rand::thread_rng();
+    for frame in frames {
+      let buf = frame.to_vec();
+      for _ in 0..2000 {
+        // flip a few random bytes of the body
+        let mut corrupted = buf.clone();
+        for _ in 0..rng.gen_range(1..4) {
+          let i = rng.gen_range(HEADER_LENGTH as usize..corrupted.len());
+          corrupted[i] = rng.gen();
+        }
+        parse_random(corrupted);
+
+        // truncate at a random position
+        let mut truncated = buf.clone();
+        truncated.truncate(rng.gen_range(0..buf.len()));
+        parse_random(truncated);
+      }
+    }
+
+    // corrupt the decompressed content instead, exercising the packed packet headers
+    for _ in 0..2000 {
+      let mut corrupted = packed.clone();
+      for _ in 0..rng.gen_range(1..4) {
+        let i = rng.gen_range(0..corrupted.len());
+        corrupted[i] = rng.gen();
+      }
+      let msg =
+        BiliWebsocketMessage::new(zlib_compress(&corrupted), OpType::Notification, PROTO_ZLIB);
+      parse_random(msg.to_vec());
+    }
+  }
 }
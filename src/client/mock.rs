@@ -0,0 +1,427 @@
+//! A mock of Bilibili's live websocket server for tests.
+//! [MockServer] speaks the real frame format: it accepts the entry packet,
+//! acknowledges it, answers heartbeats with the room popularity and replays
+//! a [Script] of notifications, batched and compressed like the real server does.
+//! Together with [stub_api], which serves the room's server list, it lets
+//! [BiliClient](super::BiliClient) and the plugins run without a live room.
+
+use std::{io::Write, sync::Arc, time::Duration};
+
+use axum::{routing::get, Json, Router};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+  io::{AsyncRead, AsyncWrite},
+  net::TcpListener,
+  sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+use tokio_native_tls::TlsAcceptor;
+use tokio_tungstenite::{accept_async, tungstenite::protocol::Message};
+
+use super::message::{BiliWebsocketMessage, OpType, BROTLI_BUFFER_SIZE, PROTO_BROTLI, PROTO_ZLIB};
+
+/// A short recorded session with danmu, gifts and an interaction
+pub const LIVE_SESSION: &str = include_str!("fixtures/live_session.json");
+
+/// How notifications are packed into frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  // one uncompressed notification per frame
+  None,
+  // batches compressed with zlib (protover 2)
+  Zlib,
+  // batches compressed with brotli (protover 3)
+  Brotli,
+}
+
+/// What the server sends to every connected client
+#[derive(Debug, Clone)]
+pub struct Script {
+  // notification bodies to replay, in order
+  pub notifications: Vec<Value>,
+  pub compression: Compression,
+  // number of notifications packed into one compressed frame
+  pub batch_size: usize,
+  // delay between frames
+  pub interval: Duration,
+  // popularity carried by heartbeat replies
+  pub popularity: i32,
+}
+
+impl Default for Script {
+  fn default() -> Self {
+    Self {
+      notifications: vec![],
+      compression: Compression::Brotli,
+      batch_size: 1,
+      interval: Duration::from_millis(10),
+      popularity: 1,
+    }
+  }
+}
+
+impl Script {
+  /// script replaying a recorded json array of notification bodies
+  pub fn from_recording(recording: &str) -> serde_json::Result<Self> {
+    Ok(Self {
+      notifications: serde_json::from_str(recording)?,
+      ..Default::default()
+    })
+  }
+
+  // frames the script sends after the entry is acknowledged
+  fn frames(&self) -> Vec<BiliWebsocketMessage> {
+    let protocol_version = match self.compression {
+      Compression::None => return self.notifications.iter().map(notification).collect(),
+      Compression::Zlib => PROTO_ZLIB,
+      Compression::Brotli => PROTO_BROTLI,
+    };
+    self
+      .notifications
+      .chunks(self.batch_size.max(1))
+      .map(|batch| {
+        let packed: Vec<u8> = batch
+          .iter()
+          .flat_map(|n| notification(n).to_vec())
+          .collect();
+        let compressed = match self.compression {
+          Compression::Zlib => zlib_compress(&packed),
+          _ => brotli_compress(&packed),
+        };
+        BiliWebsocketMessage::new(compressed, OpType::Notification, protocol_version)
+      })
+      .collect()
+  }
+}
+
+/// A running mock server, it stops when the test's runtime shuts down
+#[derive(Debug)]
+pub struct MockServer {
+  port: u16,
+  // entry packets received, in order
+  entries: UnboundedReceiver<Value>,
+}
+
+impl MockServer {
+  /// serve the script over plain ws
+  pub async fn start(script: Script) -> Self {
+    Self::serve(script, None).await
+  }
+
+  /// serve the script over TLS
+  pub async fn start_tls(script: Script, acceptor: TlsAcceptor) -> Self {
+    Self::serve(script, Some(acceptor)).await
+  }
+
+  async fn serve(script: Script, tls: Option<TlsAcceptor>) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, entries) = unbounded_channel();
+    let script = Arc::new(script);
+    tokio::spawn(async move {
+      while let Ok((stream, _)) = listener.accept().await {
+        let tx = tx.clone();
+        let tls = tls.clone();
+        let script = script.clone();
+        tokio::spawn(async move {
+          match tls {
+            Some(tls) => {
+              if let Ok(stream) = tls.accept(stream).await {
+                serve_connection(stream, &script, tx).await
+              }
+            }
+            None => serve_connection(stream, &script, tx).await,
+          }
+        });
+      }
+    });
+    Self { port, entries }
+  }
+
+  pub fn port(&self) -> u16 {
+    self.port
+  }
+
+  /// wait for the next entry packet
+  pub async fn recv_entry(&mut self) -> Value {
+    tokio::time::timeout(Duration::from_secs(10), self.entries.recv())
+      .await
+      .unwrap()
+      .unwrap()
+  }
+
+  /// whether no entry packet arrives within the duration
+  pub async fn no_entry_within(&mut self, duration: Duration) -> bool {
+    tokio::time::timeout(duration, self.entries.recv())
+      .await
+      .is_err()
+  }
+}
+
+async fn serve_connection(
+  stream: impl AsyncRead + AsyncWrite + Unpin,
+  script: &Script,
+  entries: UnboundedSender<Value>,
+) {
+  let Ok(ws) = accept_async(stream).await else {
+    return;
+  };
+  let (mut write, mut read) = ws.split();
+
+  // the first packet must be the entry
+  let Some(Ok(Message::Binary(buf))) = read.next().await else {
+    return;
+  };
+  let Ok(entry) = BiliWebsocketMessage::from_binary(buf) else {
+    return;
+  };
+  if entry.op() != OpType::Entry {
+    return;
+  }
+  let Ok(entry) = serde_json::from_slice(entry.data()) else {
+    return;
+  };
+  // nobody may be listening
+  let _ = entries.send(entry);
+  if write.send(binary(entry_reply())).await.is_err() {
+    return;
+  }
+
+  let mut frames = script.frames().into_iter().peekable();
+  let mut replay = tokio::time::interval(script.interval);
+  loop {
+    tokio::select! {
+      msg = read.next() => match msg {
+        Some(Ok(Message::Binary(buf))) => {
+          let is_heartbeat = BiliWebsocketMessage::from_binary(buf)
+            .is_ok_and(|msg| msg.op() == OpType::HeartBeat);
+          if is_heartbeat {
+            let reply = binary(heartbeat_reply(script.popularity));
+            if write.send(reply).await.is_err() {
+              return;
+            }
+          }
+        }
+        Some(Ok(_)) => (),
+        _ => return,
+      },
+      _ = replay.tick(), if frames.peek().is_some() => {
+        let frame = frames.next().unwrap();
+        if write.send(binary(frame)).await.is_err() {
+          return;
+        }
+      }
+    }
+  }
+}
+
+fn binary(msg: BiliWebsocketMessage) -> Message {
+  Message::Binary(msg.to_vec())
+}
+
+/// entry reply frame
+pub fn entry_reply() -> BiliWebsocketMessage {
+  BiliWebsocketMessage::new(br#"{"code":0}"#.to_vec(), OpType::EntryReply, 1)
+}
+
+/// heartbeat reply frame carrying the popularity
+pub fn heartbeat_reply(popularity: i32) -> BiliWebsocketMessage {
+  BiliWebsocketMessage::new(popularity.to_be_bytes().to_vec(), OpType::HeartBeatReply, 1)
+}
+
+/// uncompressed notification frame carrying the json body
+pub fn notification(body: &Value) -> BiliWebsocketMessage {
+  BiliWebsocketMessage::new(serde_json::to_vec(body).unwrap(), OpType::Notification, 0)
+}
+
+pub fn zlib_compress(buf: &[u8]) -> Vec<u8> {
+  let mut z = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+  z.write_all(buf).unwrap();
+  z.finish().unwrap()
+}
+
+pub fn brotli_compress(buf: &[u8]) -> Vec<u8> {
+  let mut compressed = vec![];
+  {
+    let mut b = brotli::CompressorWriter::new(&mut compressed, BROTLI_BUFFER_SIZE, 11, 22);
+    b.write_all(buf).unwrap();
+  }
+  compressed
+}
+
+/// a host entry of the getDanmuInfo API on localhost
+pub fn host(ws_port: u16, wss_port: u16) -> Value {
+  json!({"host": "localhost", "port": ws_port, "wss_port": wss_port, "ws_port": ws_port})
+}
+
+/// serve a stub of the getDanmuInfo API that lists the given hosts,
+/// returns the API's base url
+pub async fn stub_api(token: &'static str, host_list: Vec<Value>) -> String {
+  let body = json!({
+    "code": 0,
+    "message": "0",
+    "ttl": 1,
+    "data": {
+      "group": "live",
+      "business_id": 0,
+      "refresh_row_factor": 0.125,
+      "refresh_rate": 100,
+      "max_delay": 5000,
+      "token": token,
+      "host_list": host_list,
+    }
+  });
+  let app = Router::new().route(
+    "/xlive/web-room/v1/index/getDanmuInfo",
+    get(move || async move { Json(body) }),
+  );
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  tokio::spawn(
+    axum::Server::from_tcp(listener)
+      .unwrap()
+      .serve(app.into_make_service()),
+  );
+  format!("http://{addr}")
+}
+
+/// a self-signed certificate for localhost, returns the acceptor serving it
+/// and a connector trusting it
+pub fn self_signed() -> (TlsAcceptor, native_tls::TlsConnector) {
+  let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+  let cert_pem = cert.serialize_pem().unwrap();
+  let key_pem = cert.serialize_private_key_pem();
+
+  let identity = native_tls::Identity::from_pkcs8(cert_pem.as_bytes(), key_pem.as_bytes()).unwrap();
+  let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+  let connector = native_tls::TlsConnector::builder()
+    .add_root_certificate(native_tls::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+    .build()
+    .unwrap();
+  (acceptor.into(), connector)
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::sync::broadcast;
+
+  use super::*;
+  use crate::{
+    client::{BiliClient, BiliMessage, ClientOptions, Heartbeat, Transport},
+    plugins::{GiftThanker, PluginContext, PluginRegistry},
+    util::temp_dir,
+  };
+
+  // run a client against a mock server replaying the script
+  async fn run_client(
+    script: Script,
+  ) -> (MockServer, BiliClient, broadcast::Receiver<BiliMessage>) {
+    let server = MockServer::start(script).await;
+    let api_base = stub_api("token", vec![host(server.port(), 0)]).await;
+    let (tx, rx) = broadcast::channel(100);
+    let mut cli = BiliClient::with_options(
+      tx,
+      ClientOptions {
+        api_base,
+        heartbeat: Heartbeat {
+          interval: Duration::from_millis(50),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+    );
+    cli.start(42, Some(7), Transport::Ws).unwrap();
+    (server, cli, rx)
+  }
+
+  // danmu contents & gift names received, in order
+  async fn recv_events(rx: &mut broadcast::Receiver<BiliMessage>, count: usize) -> Vec<String> {
+    let mut events = vec![];
+    tokio::time::timeout(Duration::from_secs(10), async {
+      while events.len() < count {
+        match rx.recv().await.unwrap() {
+          BiliMessage::Danmu(danmu) => events.push(danmu.content().clone()),
+          BiliMessage::Gift(gift) => events.push(gift.gift_name().clone()),
+          _ => (),
+        }
+      }
+    })
+    .await
+    .unwrap();
+    events
+  }
+
+  fn session(compression: Compression, batch_size: usize) -> Script {
+    Script {
+      compression,
+      batch_size,
+      ..Script::from_recording(LIVE_SESSION).unwrap()
+    }
+  }
+
+  async fn assert_replays(script: Script) {
+    let (mut server, _cli, mut rx) = run_client(script).await;
+    assert_eq!(42, server.recv_entry().await["roomid"]);
+    assert_eq!(
+      vec!["晚上好", "小花花", "主播好可爱", "辣条"],
+      recv_events(&mut rx, 4).await
+    );
+  }
+
+  #[tokio::test]
+  async fn test_replay_uncompressed() {
+    assert_replays(session(Compression::None, 1)).await;
+  }
+
+  #[tokio::test]
+  async fn test_replay_zlib_batches() {
+    assert_replays(session(Compression::Zlib, 3)).await;
+  }
+
+  #[tokio::test]
+  async fn test_replay_brotli_batches() {
+    assert_replays(session(Compression::Brotli, 2)).await;
+  }
+
+  #[tokio::test]
+  async fn test_heartbeat_popularity() {
+    let (_server, _cli, mut rx) = run_client(Script {
+      popularity: 2333,
+      ..Default::default()
+    })
+    .await;
+    tokio::time::timeout(Duration::from_secs(10), async {
+      while !matches!(rx.recv().await.unwrap(), BiliMessage::RoomPopularity(2333)) {}
+    })
+    .await
+    .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_gift_thanker() {
+    let (_server, cli, rx) = run_client(session(Compression::Brotli, 5)).await;
+    let (sender_tx, mut sender_rx) = unbounded_channel();
+    let dir = temp_dir("mock-plugins");
+    let _plugins = PluginRegistry::start(
+      vec![Box::<GiftThanker>::default()],
+      rx,
+      PluginContext::new(sender_tx),
+      dir.clone(),
+    );
+
+    let mut thanks = vec![];
+    for _ in 0..2 {
+      let reply = tokio::time::timeout(Duration::from_secs(10), sender_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+      thanks.push(reply);
+    }
+    assert_eq!(
+      vec!["感谢送礼用户投喂的1个小花花~", "感谢路人投喂的5个辣条~"],
+      thanks
+    );
+    drop(cli);
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
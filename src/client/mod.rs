@@ -4,8 +4,19 @@
 mod biliclient;
 mod common;
 mod message;
+#[cfg(test)]
+pub(crate) mod mock;
+mod pipeline;
+mod recording;
 
-pub use biliclient::BiliClient;
-pub use common::{BiliMessage, DanmuMessage, GiftMessage, GuardType, Medal};
+pub use biliclient::{Backoff, BiliClient, ClientOptions, Heartbeat, RawPassthrough, Transport};
+pub use common::{
+  BiliMessage, CoinType, DanmuMessage, GiftMessage, GuardPurchaseMessage, GuardType, PkSession,
+  RoomEvent, SuperChatMessage,
+};
+
+pub use message::PROTO_BROTLI;
+pub use pipeline::DedupStats;
+pub use recording::ReplaySpeed;
 
 pub(crate) use self::message::{BiliWebsocketInner, BiliWebsocketMessageBody, NotificationBody};
@@ -0,0 +1,750 @@
+//! The path a room's frames take from the wire to the downstream consumer.
+//! Frames are parsed into [BiliMessage]s statelessly, then go through the
+//! stateful stages of the room's [Pipeline] before being forwarded.
+
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Arc,
+  time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{debug, error, warn};
+use ts_rs::TS;
+
+use super::{
+  biliclient::{Consumer, RawPassthrough, RoomStatus},
+  common::InteractionType,
+  message::BiliWebsocketMessage,
+  BiliMessage, BiliWebsocketMessageBody, GuardPurchaseMessage, GuardType,
+};
+
+// how long a GUARD_BUY waits for the USER_TOAST_MSG of the same purchase
+const GUARD_TOAST_WAIT: Duration = Duration::from_secs(2);
+// how long a forwarded purchase suppresses the other message of its pair
+const GUARD_PAIR_WINDOW: Duration = Duration::from_secs(10);
+
+/// What a frame from the server tells about the connection
+#[derive(Debug, Default)]
+pub(super) struct Received {
+  // the frame contains the server's reply to our entry
+  pub entry_reply: bool,
+  // the frame contains a reply or a notification
+  pub alive: bool,
+}
+
+/// Processing stages of a room, kept across reconnections
+#[derive(Debug)]
+pub(super) struct Pipeline {
+  room_id: i64,
+  status: Arc<RoomStatus>,
+  downstream: Consumer,
+  raw_passthrough: RawPassthrough,
+  guard_purchases: GuardPurchases,
+  dedup: Dedup,
+  // enter interactions are dropped if false
+  forward_enters: bool,
+}
+
+impl Pipeline {
+  pub fn new(
+    room_id: i64,
+    status: Arc<RoomStatus>,
+    downstream: Consumer,
+    raw_passthrough: RawPassthrough,
+    dedup_window: Duration,
+    forward_enters: bool,
+  ) -> Self {
+    Self {
+      room_id,
+      status,
+      downstream,
+      raw_passthrough,
+      guard_purchases: GuardPurchases::default(),
+      dedup: Dedup::new(dedup_window),
+      forward_enters,
+    }
+  }
+
+  pub fn room_id(&self) -> i64 {
+    self.room_id
+  }
+
+  pub fn status(&self) -> &Arc<RoomStatus> {
+    &self.status
+  }
+
+  /// Parses a websocket frame and forwards the messages in it downstream,
+  /// malformed packets are logged and skipped.
+  /// Heartbeat replies are recorded to measure the latency
+  pub fn forward_frame(&mut self, buf: Vec<u8>) -> Received {
+    self.forward_expired();
+
+    let mut received = Received::default();
+    let msg = match BiliWebsocketMessage::from_binary(buf) {
+      Ok(msg) => msg,
+      Err(err) => {
+        warn!("Room {} Received Malformed Frame: {}", self.room_id, err);
+        return received;
+      }
+    };
+    for inner in msg.parse() {
+      let inner = match inner {
+        Ok(inner) => inner,
+        Err(err) => {
+          warn!("Room {} Received Malformed Packet: {}", self.room_id, err);
+          continue;
+        }
+      };
+      received.alive = true;
+      let mut from_toast = false;
+      match inner.body() {
+        BiliWebsocketMessageBody::EntryReply => received.entry_reply = true,
+        BiliWebsocketMessageBody::RoomPopularity(_) => self.status.heartbeat_replied(),
+        BiliWebsocketMessageBody::Notification(body) => {
+          from_toast = body.get("cmd").and_then(Value::as_str) == Some("USER_TOAST_MSG");
+        }
+      }
+      let bili_msg = match BiliMessage::from_raw_wesocket_message(inner) {
+        Some(BiliMessage::GuardPurchase(purchase)) => {
+          let now = Instant::now();
+          if from_toast {
+            self.guard_purchases.on_toast(purchase, now)
+          } else {
+            self.guard_purchases.on_guard_buy(purchase, now)
+          }
+          .map(BiliMessage::GuardPurchase)
+        }
+        Some(BiliMessage::Interaction(interaction))
+          if !self.forward_enters && *interaction.kind() == InteractionType::Enter =>
+        {
+          None
+        }
+        Some(BiliMessage::Pk(event)) => {
+          self.status.update_pk(self.room_id, &event);
+          Some(BiliMessage::Pk(event))
+        }
+        Some(BiliMessage::Raw { cmd, body }) => {
+          self.status.count_unhandled(&cmd);
+          self
+            .raw_passthrough
+            .forwards(&cmd)
+            .then_some(BiliMessage::Raw { cmd, body })
+        }
+        msg => msg,
+      };
+      if let Some(msg) = bili_msg {
+        if self.is_duplicate(&msg) {
+          debug!("Room {} Dropped Duplicate {:?}", self.room_id, msg);
+          continue;
+        }
+        self.publish(msg);
+      }
+    }
+    received
+  }
+
+  /// when a message held by the stages is due, None if nothing is held.
+  /// The connection calls [Pipeline::forward_expired] then, so that held
+  /// messages don't wait for the next frame
+  pub fn next_deadline(&self) -> Option<Instant> {
+    self.guard_purchases.next_deadline()
+  }
+
+  /// forward the held messages that are due
+  pub fn forward_expired(&mut self) {
+    for purchase in self.guard_purchases.expired(Instant::now()) {
+      self.publish(BiliMessage::GuardPurchase(purchase));
+    }
+  }
+
+  /// forward the messages held by the stages, called when a connection ends
+  pub fn flush(&mut self) {
+    for purchase in self.guard_purchases.drain() {
+      self.publish(BiliMessage::GuardPurchase(purchase));
+    }
+  }
+
+  pub fn publish(&self, msg: BiliMessage) {
+    publish(&self.downstream, msg);
+  }
+
+  // whether the message has been forwarded within the dedup window
+  fn is_duplicate(&mut self, msg: &BiliMessage) -> bool {
+    let Some(keys) = DedupKeys::of(msg) else {
+      return false;
+    };
+    let duplicate = self.dedup.check(keys, Instant::now());
+    self.status.count_dedup(msg, duplicate);
+    duplicate
+  }
+}
+
+pub(super) fn publish(downstream: &Consumer, msg: BiliMessage) {
+  if let Err(err) = downstream.send(msg) {
+    error!("{}", err);
+  }
+}
+
+/// How many messages with an id the dedup stage has seen,
+/// and how many of each kind were dropped as duplicates
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/DedupStats.ts")]
+pub struct DedupStats {
+  #[ts(type = "number")]
+  pub checked: u64,
+  #[ts(type = "number")]
+  pub danmu: u64,
+  #[ts(type = "number")]
+  pub gift: u64,
+  #[ts(type = "number")]
+  pub super_chat: u64,
+}
+
+impl DedupStats {
+  pub(super) fn count(&mut self, msg: &BiliMessage, duplicate: bool) {
+    self.checked += 1;
+    if !duplicate {
+      return;
+    }
+    match msg {
+      BiliMessage::Danmu(_) => self.danmu += 1,
+      BiliMessage::Gift(_) => self.gift += 1,
+      BiliMessage::SuperChat(_) => self.super_chat += 1,
+      _ => {}
+    }
+  }
+}
+
+// ids a message is known by
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DedupKey {
+  Danmu(String),
+  GiftTid(String),
+  // a SEND_GIFT of the batch was forwarded
+  GiftBatch(String),
+  // a COMBO_SEND of the batch, with the number of gifts it sums up
+  GiftCombo(String, u64),
+  SuperChat(u64),
+}
+
+// the key a message is checked against, the other keys it is known by,
+// and the keys of messages that already account for it
+#[derive(Debug)]
+struct DedupKeys {
+  key: DedupKey,
+  aliases: Vec<DedupKey>,
+  covered_by: Vec<DedupKey>,
+}
+
+impl DedupKeys {
+  // None for messages without an id
+  fn of(msg: &BiliMessage) -> Option<DedupKeys> {
+    let (key, aliases, covered_by) = match msg {
+      BiliMessage::Danmu(danmu) => (DedupKey::Danmu(danmu.id_str().clone()?), vec![], vec![]),
+      // a SEND_GIFT is known by its transaction alone, and marks its batch
+      // as sent, so that the COMBO_SEND summing the batch up is dropped
+      BiliMessage::Gift(gift) => match (gift.tid(), gift.batch_combo_id()) {
+        (Some(tid), batch) => (
+          DedupKey::GiftTid(tid.clone()),
+          batch.clone().map(DedupKey::GiftBatch).into_iter().collect(),
+          vec![],
+        ),
+        (None, Some(batch)) => (
+          DedupKey::GiftCombo(batch.clone(), *gift.gift_num()),
+          vec![],
+          vec![DedupKey::GiftBatch(batch.clone())],
+        ),
+        (None, None) => return None,
+      },
+      BiliMessage::SuperChat(sc) => (DedupKey::SuperChat(*sc.id()), vec![], vec![]),
+      _ => return None,
+    };
+    Some(DedupKeys {
+      key,
+      aliases,
+      covered_by,
+    })
+  }
+}
+
+/// Drops messages whose id was seen within the window, e.g. those pushed
+/// again after a reconnection. A zero window disables the stage
+#[derive(Debug)]
+struct Dedup {
+  window: Duration,
+  // keys with when they were last seen
+  seen: HashMap<DedupKey, Instant>,
+  // keys in the order they were seen, with when they were seen
+  expiry: VecDeque<(Instant, DedupKey)>,
+}
+
+impl Dedup {
+  fn new(window: Duration) -> Self {
+    Self {
+      window,
+      seen: HashMap::new(),
+      expiry: VecDeque::new(),
+    }
+  }
+
+  fn check(&mut self, keys: DedupKeys, now: Instant) -> bool {
+    if self.window.is_zero() {
+      return false;
+    }
+    while let Some((seen, _)) = self.expiry.front() {
+      if now.duration_since(*seen) < self.window {
+        break;
+      }
+      let (seen, key) = self.expiry.pop_front().unwrap();
+      // keys seen again since are kept
+      if self.seen.get(&key) == Some(&seen) {
+        self.seen.remove(&key);
+      }
+    }
+
+    let seen = |key: &DedupKey| self.seen.contains_key(key);
+    if seen(&keys.key) || keys.covered_by.iter().any(seen) {
+      return true;
+    }
+    // seeing a key again, e.g. the batch of another gift, restarts its window
+    for key in std::iter::once(keys.key).chain(keys.aliases) {
+      self.seen.insert(key.clone(), now);
+      self.expiry.push_back((now, key));
+    }
+    false
+  }
+}
+
+/// Bilibili announces a guard purchase twice, as GUARD_BUY and then as
+/// USER_TOAST_MSG. The toast is preferred because it tells renewals apart,
+/// so a GUARD_BUY waits a while for its toast and is only forwarded if
+/// the toast doesn't come.
+#[derive(Debug, Default)]
+struct GuardPurchases {
+  // GUARD_BUYs waiting for their toast, with when they arrived
+  pending: Vec<(Instant, GuardPurchaseMessage)>,
+  // purchases forwarded recently, with when they were forwarded
+  forwarded: VecDeque<(Instant, u64, GuardType)>,
+}
+
+impl GuardPurchases {
+  fn on_guard_buy(
+    &mut self,
+    purchase: GuardPurchaseMessage,
+    now: Instant,
+  ) -> Option<GuardPurchaseMessage> {
+    if !self.is_forwarded(&purchase, now) {
+      self.pending.push((now, purchase));
+    }
+    None
+  }
+
+  fn on_toast(
+    &mut self,
+    purchase: GuardPurchaseMessage,
+    now: Instant,
+  ) -> Option<GuardPurchaseMessage> {
+    self
+      .pending
+      .retain(|(_, pending)| !same_purchase(pending, &purchase));
+    if self.is_forwarded(&purchase, now) {
+      return None;
+    }
+    self.mark_forwarded(&purchase, now);
+    Some(purchase)
+  }
+
+  // when the earliest GUARD_BUY stops waiting for its toast
+  fn next_deadline(&self) -> Option<Instant> {
+    self
+      .pending
+      .iter()
+      .map(|(arrived, _)| *arrived + GUARD_TOAST_WAIT)
+      .min()
+  }
+
+  // GUARD_BUYs whose toast didn't come in time
+  fn expired(&mut self, now: Instant) -> Vec<GuardPurchaseMessage> {
+    let (expired, pending) = std::mem::take(&mut self.pending)
+      .into_iter()
+      .partition(|(arrived, _)| now.duration_since(*arrived) >= GUARD_TOAST_WAIT);
+    self.pending = pending;
+    expired
+      .into_iter()
+      .map(|(_, purchase)| {
+        self.mark_forwarded(&purchase, now);
+        purchase
+      })
+      .collect()
+  }
+
+  // all GUARD_BUYs still waiting
+  fn drain(&mut self) -> Vec<GuardPurchaseMessage> {
+    let now = Instant::now();
+    std::mem::take(&mut self.pending)
+      .into_iter()
+      .map(|(_, purchase)| {
+        self.mark_forwarded(&purchase, now);
+        purchase
+      })
+      .collect()
+  }
+
+  fn is_forwarded(&mut self, purchase: &GuardPurchaseMessage, now: Instant) -> bool {
+    while let Some((forwarded, _, _)) = self.forwarded.front() {
+      if now.duration_since(*forwarded) < GUARD_PAIR_WINDOW {
+        break;
+      }
+      self.forwarded.pop_front();
+    }
+    self
+      .forwarded
+      .iter()
+      .any(|(_, uid, guard)| *uid == *purchase.uid() && guard == purchase.guard())
+  }
+
+  fn mark_forwarded(&mut self, purchase: &GuardPurchaseMessage, now: Instant) {
+    self
+      .forwarded
+      .push_back((now, *purchase.uid(), *purchase.guard()));
+  }
+}
+
+// GUARD_BUY and USER_TOAST_MSG of one purchase share the user and the guard level
+fn same_purchase(a: &GuardPurchaseMessage, b: &GuardPurchaseMessage) -> bool {
+  a.uid() == b.uid() && a.guard() == b.guard()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::client::mock::notification;
+
+  fn guard_buy() -> Value {
+    serde_json::from_str(include_str!("fixtures/guard_buy.json")).unwrap()
+  }
+
+  fn user_toast() -> Value {
+    serde_json::from_str(include_str!("fixtures/user_toast_msg.json")).unwrap()
+  }
+
+  fn pipeline() -> (Pipeline, tokio::sync::broadcast::Receiver<BiliMessage>) {
+    let (tx, rx) = tokio::sync::broadcast::channel(100);
+    (
+      Pipeline::new(
+        42,
+        Arc::default(),
+        tx,
+        RawPassthrough::Off,
+        Duration::ZERO,
+        true,
+      ),
+      rx,
+    )
+  }
+
+  fn purchases(
+    rx: &mut tokio::sync::broadcast::Receiver<BiliMessage>,
+  ) -> Vec<GuardPurchaseMessage> {
+    let mut purchases = vec![];
+    while let Ok(msg) = rx.try_recv() {
+      if let BiliMessage::GuardPurchase(purchase) = msg {
+        purchases.push(purchase);
+      }
+    }
+    purchases
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_guard_buy_then_toast() {
+    let (mut pipeline, mut rx) = pipeline();
+    pipeline.forward_frame(notification(&guard_buy()).to_vec());
+    // waiting for the toast
+    assert!(purchases(&mut rx).is_empty());
+
+    tokio::time::advance(Duration::from_millis(500)).await;
+    pipeline.forward_frame(notification(&user_toast()).to_vec());
+    let forwarded = purchases(&mut rx);
+    assert_eq!(1, forwarded.len());
+    assert!(forwarded[0].is_renewal());
+
+    // nothing is left behind
+    tokio::time::advance(GUARD_TOAST_WAIT).await;
+    pipeline.flush();
+    assert!(purchases(&mut rx).is_empty());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_toast_then_guard_buy() {
+    let (mut pipeline, mut rx) = pipeline();
+    pipeline.forward_frame(notification(&user_toast()).to_vec());
+    pipeline.forward_frame(notification(&guard_buy()).to_vec());
+    tokio::time::advance(GUARD_TOAST_WAIT).await;
+    pipeline.flush();
+    assert_eq!(1, purchases(&mut rx).len());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_guard_buy_without_toast() {
+    let (mut pipeline, mut rx) = pipeline();
+    pipeline.forward_frame(notification(&guard_buy()).to_vec());
+
+    let deadline = pipeline.next_deadline().unwrap();
+    assert_eq!(Instant::now() + GUARD_TOAST_WAIT, deadline);
+
+    // forwarded once the wait is over, without another frame
+    tokio::time::sleep_until(deadline).await;
+    pipeline.forward_expired();
+    assert!(pipeline.next_deadline().is_none());
+    let forwarded = purchases(&mut rx);
+    assert_eq!(1, forwarded.len());
+    assert!(!forwarded[0].is_renewal());
+
+    // a late toast is dropped
+    pipeline.forward_frame(notification(&user_toast()).to_vec());
+    assert!(purchases(&mut rx).is_empty());
+
+    // a purchase after the window is a new one
+    tokio::time::advance(GUARD_PAIR_WINDOW).await;
+    pipeline.forward_frame(notification(&user_toast()).to_vec());
+    assert_eq!(1, purchases(&mut rx).len());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_flush_pending_guard_buy() {
+    let (mut pipeline, mut rx) = pipeline();
+    pipeline.forward_frame(notification(&guard_buy()).to_vec());
+    pipeline.flush();
+    assert_eq!(1, purchases(&mut rx).len());
+  }
+
+  fn raw_cmds(rx: &mut tokio::sync::broadcast::Receiver<BiliMessage>) -> Vec<String> {
+    let mut cmds = vec![];
+    while let Ok(msg) = rx.try_recv() {
+      if let BiliMessage::Raw { cmd, .. } = msg {
+        cmds.push(cmd);
+      }
+    }
+    cmds
+  }
+
+  #[tokio::test]
+  async fn test_raw_passthrough() {
+    let (tx, mut rx) = tokio::sync::broadcast::channel(100);
+    let status = Arc::new(RoomStatus::default());
+    let passthrough = RawPassthrough::Cmds(["HOT_RANK_CHANGED".to_string()].into());
+    let mut pipeline = Pipeline::new(42, status.clone(), tx, passthrough, Duration::ZERO, true);
+    for cmd in [
+      "HOT_RANK_CHANGED",
+      "STOP_LIVE_ROOM_LIST",
+      "HOT_RANK_CHANGED",
+    ] {
+      pipeline.forward_frame(notification(&serde_json::json!({"cmd": cmd})).to_vec());
+    }
+    // typed commands are neither forwarded raw nor counted
+    pipeline.forward_frame(notification(&guard_buy()).to_vec());
+
+    assert_eq!(
+      vec!["HOT_RANK_CHANGED", "HOT_RANK_CHANGED"],
+      raw_cmds(&mut rx)
+    );
+    let unhandled = status.unhandled();
+    assert_eq!(2, unhandled.len());
+    assert_eq!(Some(&2), unhandled.get("HOT_RANK_CHANGED"));
+    assert_eq!(Some(&1), unhandled.get("STOP_LIVE_ROOM_LIST"));
+  }
+
+  #[tokio::test]
+  async fn test_raw_passthrough_off() {
+    let (mut pipeline, mut rx) = pipeline();
+    pipeline.forward_frame(notification(&serde_json::json!({"cmd": "HOT_RANK_CHANGED"})).to_vec());
+    assert!(raw_cmds(&mut rx).is_empty());
+    assert_eq!(
+      Some(&1),
+      pipeline.status().unhandled().get("HOT_RANK_CHANGED")
+    );
+  }
+
+  #[tokio::test]
+  async fn test_drop_enters() {
+    let (tx, mut rx) = tokio::sync::broadcast::channel(100);
+    let mut pipeline = Pipeline::new(
+      42,
+      Arc::default(),
+      tx,
+      RawPassthrough::Off,
+      Duration::ZERO,
+      false,
+    );
+    let mut interaction: Value =
+      serde_json::from_str(include_str!("fixtures/interact_word.json")).unwrap();
+    // enter
+    interaction["data"]["msg_type"] = 1.into();
+    pipeline.forward_frame(notification(&interaction).to_vec());
+    assert!(rx.try_recv().is_err());
+    // follow
+    interaction["data"]["msg_type"] = 2.into();
+    pipeline.forward_frame(notification(&interaction).to_vec());
+    assert!(matches!(rx.try_recv(), Ok(BiliMessage::Interaction(_))));
+  }
+
+  #[tokio::test]
+  async fn test_pk_snapshot() {
+    let (tx, mut rx) = tokio::sync::broadcast::channel(100);
+    let status = Arc::new(RoomStatus::default());
+    let mut pipeline = Pipeline::new(
+      12345,
+      status.clone(),
+      tx,
+      RawPassthrough::Off,
+      Duration::ZERO,
+      true,
+    );
+    for json in [
+      include_str!("fixtures/pk_battle_pre_new.json"),
+      include_str!("fixtures/pk_battle_process_new.json"),
+    ] {
+      let event: Value = serde_json::from_str(json).unwrap();
+      pipeline.forward_frame(notification(&event).to_vec());
+    }
+
+    // the events are forwarded as well
+    let mut events = 0;
+    while let Ok(msg) = rx.try_recv() {
+      assert!(matches!(msg, BiliMessage::Pk(_)));
+      events += 1;
+    }
+    assert_eq!(2, events);
+
+    let pk = status.pk().unwrap();
+    assert_eq!("对手主播", pk.opponent_uname());
+    assert_eq!((100, 50), (*pk.score(), *pk.opponent_score()));
+  }
+
+  fn dedup_pipeline(
+    window: Duration,
+  ) -> (
+    Pipeline,
+    Arc<RoomStatus>,
+    tokio::sync::broadcast::Receiver<BiliMessage>,
+  ) {
+    let (tx, rx) = tokio::sync::broadcast::channel(100);
+    let status = Arc::new(RoomStatus::default());
+    let pipeline = Pipeline::new(42, status.clone(), tx, RawPassthrough::Off, window, true);
+    (pipeline, status, rx)
+  }
+
+  fn forward_fixture(pipeline: &mut Pipeline, json: &str) {
+    let body: Value = serde_json::from_str(json).unwrap();
+    pipeline.forward_frame(notification(&body).to_vec());
+  }
+
+  fn count(rx: &mut tokio::sync::broadcast::Receiver<BiliMessage>) -> usize {
+    let mut count = 0;
+    while rx.try_recv().is_ok() {
+      count += 1;
+    }
+    count
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_dedup_pushed_again() {
+    let (mut pipeline, status, mut rx) = dedup_pipeline(Duration::from_secs(60));
+    let danmu = include_str!("fixtures/danmu_msg.json");
+    let super_chat = include_str!("fixtures/super_chat_message.json");
+    forward_fixture(&mut pipeline, danmu);
+    forward_fixture(&mut pipeline, super_chat);
+    assert_eq!(2, count(&mut rx));
+
+    // pushed again after a reconnection
+    forward_fixture(&mut pipeline, danmu);
+    forward_fixture(&mut pipeline, super_chat);
+    assert_eq!(0, count(&mut rx));
+
+    // remembered only within the window
+    tokio::time::advance(Duration::from_secs(60)).await;
+    forward_fixture(&mut pipeline, danmu);
+    assert_eq!(1, count(&mut rx));
+
+    assert_eq!(
+      DedupStats {
+        checked: 5,
+        danmu: 1,
+        gift: 0,
+        super_chat: 1,
+      },
+      status.dedup_stats()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_dedup_combo_after_gift() {
+    let (mut pipeline, status, mut rx) = dedup_pipeline(Duration::from_secs(60));
+    let mut gift: Value = serde_json::from_str(include_str!("fixtures/send_gift.json")).unwrap();
+    let mut combo: Value = serde_json::from_str(include_str!("fixtures/combo_send.json")).unwrap();
+    combo["data"]["batch_combo_id"] = gift["data"]["batch_combo_id"].clone();
+    pipeline.forward_frame(notification(&gift).to_vec());
+    // another gift of the batch is a different transaction
+    gift["data"]["tid"] = "1650000004110200003".into();
+    pipeline.forward_frame(notification(&gift).to_vec());
+    pipeline.forward_frame(notification(&gift).to_vec());
+    // the combo sums up the gifts already forwarded
+    pipeline.forward_frame(notification(&combo).to_vec());
+    assert_eq!(2, count(&mut rx));
+    assert_eq!(2, status.dedup_stats().gift);
+
+    // a combo of gifts not seen is forwarded once
+    let mut other = combo.clone();
+    other["data"]["batch_combo_id"] = "batch:gift:combo_id:other".into();
+    pipeline.forward_frame(notification(&other).to_vec());
+    pipeline.forward_frame(notification(&other).to_vec());
+    assert_eq!(1, count(&mut rx));
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_dedup_combo_outlives_window() {
+    let (mut pipeline, status, mut rx) = dedup_pipeline(Duration::from_secs(60));
+    let mut gift: Value = serde_json::from_str(include_str!("fixtures/send_gift.json")).unwrap();
+    let mut combo: Value = serde_json::from_str(include_str!("fixtures/combo_send.json")).unwrap();
+    combo["data"]["batch_combo_id"] = gift["data"]["batch_combo_id"].clone();
+    pipeline.forward_frame(notification(&gift).to_vec());
+    assert_eq!(1, count(&mut rx));
+
+    // the batch is forgotten after the window, so the combo is forwarded
+    tokio::time::advance(Duration::from_secs(61)).await;
+    pipeline.forward_frame(notification(&combo).to_vec());
+    // and pushed again, it is dropped
+    pipeline.forward_frame(notification(&combo).to_vec());
+    assert_eq!(1, count(&mut rx));
+
+    // gifts continuing the combo are new transactions
+    gift["data"]["tid"] = "1650000004110200003".into();
+    pipeline.forward_frame(notification(&gift).to_vec());
+    assert_eq!(1, count(&mut rx));
+    assert_eq!(1, status.dedup_stats().gift);
+
+    // every gift of the batch restarts its window
+    tokio::time::advance(Duration::from_secs(50)).await;
+    gift["data"]["tid"] = "1650000004110200004".into();
+    pipeline.forward_frame(notification(&gift).to_vec());
+    tokio::time::advance(Duration::from_secs(50)).await;
+    combo["data"]["combo_num"] = 3.into();
+    pipeline.forward_frame(notification(&combo).to_vec());
+    assert_eq!(1, count(&mut rx));
+    assert_eq!(2, status.dedup_stats().gift);
+  }
+
+  #[tokio::test]
+  async fn test_dedup_disabled() {
+    let (mut pipeline, status, mut rx) = dedup_pipeline(Duration::ZERO);
+    let danmu = include_str!("fixtures/danmu_msg.json");
+    forward_fixture(&mut pipeline, danmu);
+    forward_fixture(&mut pipeline, danmu);
+    assert_eq!(2, count(&mut rx));
+    assert_eq!(0, status.dedup_stats().danmu);
+  }
+}
@@ -0,0 +1,259 @@
+//! Recording and replaying of raw live-room sessions.
+//!
+//! A recording is a sequence of records, each holding one websocket
+//! frame exactly as received from Bilibili:
+//! [8 bytes offset in milliseconds since recording started][4 bytes frame length][frame]
+//! Frames are kept raw, including malformed ones, so that replaying a
+//! recording runs them through the same parsing pipeline as a live connection.
+
+use std::{
+  fs::{File, OpenOptions},
+  io::{BufReader, BufWriter, ErrorKind, Read, Write},
+  path::Path,
+  str::FromStr,
+  sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+  thread::JoinHandle,
+  time::Duration,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::{error::DanmujiError, DanmujiResult};
+
+// buffered frames are written out at least this often
+pub(crate) const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes frames received from a live connection to a recording file.
+/// Frames are written by a thread of its own, so that a slow disk
+/// doesn't hold up the connection
+#[derive(Debug)]
+pub struct Recorder {
+  // frames with their offsets in milliseconds, to the writer thread
+  frames: Sender<(u64, Vec<u8>)>,
+  writer: JoinHandle<std::io::Result<()>>,
+  // offsets are measured from here
+  started: Instant,
+}
+
+impl Recorder {
+  /// create the recording file, truncating it if it exists
+  pub fn create(path: impl AsRef<Path>) -> DanmujiResult<Self> {
+    let file = OpenOptions::new()
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .open(path)?;
+    let (frames, rx) = mpsc::channel();
+    let writer = std::thread::spawn(move || write_frames(rx, BufWriter::new(file)));
+    Ok(Self {
+      frames,
+      writer,
+      started: Instant::now(),
+    })
+  }
+
+  /// append a frame, fails once the writer has stopped on an error
+  pub fn record(&self, frame: &[u8]) -> DanmujiResult<()> {
+    let offset = self.started.elapsed().as_millis() as u64;
+    self
+      .frames
+      .send((offset, frame.to_vec()))
+      .map_err(|_| DanmujiError::RecordingStopped)
+  }
+
+  /// wait for the frames recorded to be written out
+  pub fn finish(self) -> DanmujiResult<()> {
+    drop(self.frames);
+    match self.writer.join() {
+      Ok(written) => Ok(written?),
+      Err(_) => Err(DanmujiError::RecordingStopped),
+    }
+  }
+}
+
+// write the frames received until the recorder is dropped, flushed
+// periodically so that the recording is usable even if we're killed.
+// Stops at the first error, which is reported by [Recorder::finish]
+fn write_frames(rx: Receiver<(u64, Vec<u8>)>, mut writer: BufWriter<File>) -> std::io::Result<()> {
+  let mut flushed = std::time::Instant::now();
+  loop {
+    match rx.recv_timeout(FLUSH_INTERVAL) {
+      Ok((offset, frame)) => {
+        writer.write_u64::<BigEndian>(offset)?;
+        writer.write_u32::<BigEndian>(frame.len() as u32)?;
+        writer.write_all(&frame)?;
+      }
+      Err(RecvTimeoutError::Timeout) => {}
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+    if flushed.elapsed() >= FLUSH_INTERVAL {
+      writer.flush()?;
+      flushed = std::time::Instant::now();
+    }
+  }
+  writer.flush()
+}
+
+/// A frame read from a recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+  // when the frame was received, relative to the start of the recording
+  pub offset: Duration,
+  pub frame: Vec<u8>,
+}
+
+/// read all frames of a recording. A record truncated at the end of the
+/// file, which happens when the recorder is killed while writing, is dropped
+pub fn read_recording(path: impl AsRef<Path>) -> DanmujiResult<Vec<RecordedFrame>> {
+  let mut reader = BufReader::new(File::open(path)?);
+  let mut frames = vec![];
+  loop {
+    match read_frame(&mut reader) {
+      Ok(Some(frame)) => frames.push(frame),
+      Ok(None) => break,
+      Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+        warn!("Recording Ends with a Truncated Frame");
+        break;
+      }
+      Err(err) => return Err(err.into()),
+    }
+  }
+  Ok(frames)
+}
+
+// read the next record, None at the end of the recording
+fn read_frame(reader: &mut impl Read) -> std::io::Result<Option<RecordedFrame>> {
+  let offset = match reader.read_u64::<BigEndian>() {
+    Ok(offset) => offset,
+    // clean end of file
+    Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+    Err(err) => return Err(err),
+  };
+  let length = reader.read_u32::<BigEndian>()?;
+  let mut frame = vec![0; length as usize];
+  reader.read_exact(&mut frame)?;
+  Ok(Some(RecordedFrame {
+    offset: Duration::from_millis(offset),
+    frame,
+  }))
+}
+
+/// How fast a recording is replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+  /// frames are delivered with their recorded timing
+  Realtime,
+  /// recorded delays are divided by the factor
+  Accelerated(f64),
+  /// frames are delivered as fast as possible
+  Instant,
+}
+
+impl ReplaySpeed {
+  /// how long after the replay starts the frame is delivered
+  pub fn scale(&self, offset: Duration) -> Duration {
+    match *self {
+      ReplaySpeed::Realtime => offset,
+      // a tiny factor would overflow the delay, which is then clamped
+      ReplaySpeed::Accelerated(factor) => {
+        Duration::try_from_secs_f64(offset.as_secs_f64() / factor).unwrap_or(Duration::MAX)
+      }
+      ReplaySpeed::Instant => Duration::ZERO,
+    }
+  }
+}
+
+/// parse from "realtime", "instant", or an acceleration factor like "4"
+impl FromStr for ReplaySpeed {
+  type Err = DanmujiError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "realtime" => Ok(ReplaySpeed::Realtime),
+      "instant" => Ok(ReplaySpeed::Instant),
+      factor => match factor.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(ReplaySpeed::Accelerated(factor)),
+        _ => Err(DanmujiError::InvalidReplaySpeed(s.to_string())),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::temp_dir;
+
+  #[test]
+  fn test_record_round_trip() {
+    let dir = temp_dir("round-trip");
+    let path = dir.join("session.rec");
+    let recorder = Recorder::create(&path).unwrap();
+    recorder.record(b"first").unwrap();
+    recorder.record(b"").unwrap();
+    recorder.record(b"third").unwrap();
+    recorder.finish().unwrap();
+
+    let frames = read_recording(&path).unwrap();
+    let contents: Vec<&[u8]> = frames.iter().map(|f| f.frame.as_slice()).collect();
+    assert_eq!(vec![&b"first"[..], b"", b"third"], contents);
+    // offsets don't go back in time
+    assert!(frames.windows(2).all(|w| w[0].offset <= w[1].offset));
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_truncated_recording() {
+    let dir = temp_dir("truncated");
+    let path = dir.join("session.rec");
+    let recorder = Recorder::create(&path).unwrap();
+    recorder.record(b"complete").unwrap();
+    recorder.record(b"truncated").unwrap();
+    recorder.finish().unwrap();
+
+    let len = std::fs::metadata(&path).unwrap().len();
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - 3).unwrap();
+
+    let frames = read_recording(&path).unwrap();
+    assert_eq!(1, frames.len());
+    assert_eq!(b"complete", frames[0].frame.as_slice());
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_missing_recording() {
+    let dir = temp_dir("missing");
+    assert!(read_recording(dir.join("session.rec")).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_replay_speed() {
+    assert_eq!(
+      Ok(ReplaySpeed::Realtime),
+      "realtime".parse().map_err(|_| ())
+    );
+    assert_eq!(Ok(ReplaySpeed::Instant), "instant".parse().map_err(|_| ()));
+    assert_eq!(
+      Ok(ReplaySpeed::Accelerated(4.0)),
+      "4".parse().map_err(|_| ())
+    );
+    assert!("0".parse::<ReplaySpeed>().is_err());
+    assert!("-1".parse::<ReplaySpeed>().is_err());
+    assert!("fast".parse::<ReplaySpeed>().is_err());
+
+    let offset = Duration::from_secs(8);
+    assert_eq!(offset, ReplaySpeed::Realtime.scale(offset));
+    assert_eq!(
+      Duration::from_secs(2),
+      ReplaySpeed::Accelerated(4.0).scale(offset)
+    );
+    assert_eq!(Duration::ZERO, ReplaySpeed::Instant.scale(offset));
+
+    let slow: ReplaySpeed = "1e-300".parse().unwrap();
+    assert_eq!(Duration::MAX, slow.scale(offset));
+  }
+}
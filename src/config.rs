@@ -1,12 +1,17 @@
 //! Configuration Types for Danmuji
 
-use crate::{error::DanmujiError, DanmujiResult, USER_AGENT};
+use crate::{
+  client::{RoomEvent, Transport},
+  error::DanmujiError,
+  DanmujiResult, USER_AGENT,
+};
 use std::collections::HashMap;
 
 use rand::Rng;
 use reqwest::header::{HeaderMap, HeaderValue};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tracing::warn;
 use ts_rs::TS;
 
 /// User Configuration
@@ -133,6 +138,9 @@ impl Cookie {
 pub struct RoomConfig {
   pub room_init: RoomInit,
   pub room: Room,
+  // preferred transport to connect to the room
+  #[serde(default)]
+  pub transport: Transport,
 }
 
 impl RoomConfig {
@@ -192,9 +200,40 @@ impl RoomConfig {
       .get_mut("data")
       .ok_or(DanmujiError::APIFormatError)?
       .take();
-    let room: Room = serde_json::from_value(room)?;
+    let mut room: Room = serde_json::from_value(room)?;
+
+    // the title is only shown to the user, so failing to get it
+    // doesn't fail the connection
+    room.title = match Self::fetch_title(&cli, room_init.room_id).await {
+      Ok(title) => title,
+      Err(err) => {
+        warn!("Fail Fetching Title of Room {}: {}", room_init.room_id, err);
+        String::new()
+      }
+    };
+    room.live_status = room_init.live_status;
 
-    Ok(RoomConfig { room_init, room })
+    Ok(RoomConfig {
+      room_init,
+      room,
+      transport: Transport::default(),
+    })
+  }
+
+  // api reference: https://github.com/lovelyyoshino/Bilibili-Live-API/blob/master/API.getRoomInfo.md
+  async fn fetch_title(cli: &reqwest::Client, room_id: i64) -> DanmujiResult<String> {
+    let res = cli
+      .get(format!(
+        "https://api.live.bilibili.com/room/v1/Room/get_info?room_id={room_id}"
+      ))
+      .send()
+      .await?;
+    let res: Value = res.json().await?;
+    let title = res
+      .pointer("/data/title")
+      .and_then(Value::as_str)
+      .ok_or(DanmujiError::APIFormatError)?;
+    Ok(title.to_string())
   }
 }
 
@@ -207,8 +246,8 @@ pub struct RoomInit {
   pub is_hidden: bool,
   pub is_locked: bool,
   pub is_portrait: bool,
-  // 0 -> is live
-  // 1 -> is not live
+  // 0 -> is not live
+  // 1 -> is live
   // 2 -> streaming recorded vedio
   pub live_status: i32,
   pub hidden_till: i32,
@@ -221,6 +260,28 @@ pub struct RoomInit {
   pub special_type: i32,
 }
 
+impl RoomConfig {
+  /// keep the room's information up to date with what happens in it
+  pub fn apply_event(&mut self, event: &RoomEvent) {
+    match event {
+      RoomEvent::Live { live_time } => {
+        self.set_live_status(1);
+        if let Some(live_time) = live_time {
+          self.room_init.live_time = *live_time as i64;
+        }
+      }
+      RoomEvent::Preparing | RoomEvent::CutOff { .. } => self.set_live_status(0),
+      RoomEvent::RoomChange { title, .. } => self.room.title = title.clone(),
+      RoomEvent::Warning { .. } => {}
+    }
+  }
+
+  fn set_live_status(&mut self, live_status: i32) {
+    self.room_init.live_status = live_status;
+    self.room.live_status = live_status;
+  }
+}
+
 impl RoomInit {
   pub fn effective_room_id(&self) -> i64 {
     if self.short_id > 0 {
@@ -239,7 +300,7 @@ pub struct Room {
   pub roomid: String,
   // streamer's user id
   pub uid: String,
-  // live's title
+  // streamer's announcement (主播公告)
   pub content: String,
   // unknown time field
   pub ctime: String,
@@ -247,6 +308,12 @@ pub struct Room {
   pub status: String,
   // streamer's user name
   pub uname: String,
+  // live's title, not part of the RoomNews API
+  #[serde(default)]
+  pub title: String,
+  // same as [RoomInit]'s live_status, not part of the RoomNews API
+  #[serde(default)]
+  pub live_status: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -261,42 +328,58 @@ pub struct WsConfig {
 }
 
 impl WsConfig {
-  #[allow(dead_code)]
-  pub fn get_ws_url(&self) -> Option<String> {
-    if self.host_list.is_empty() {
-      return None;
+  /// fetch the websocket servers & auth token of the given room
+  ///
+  /// * `api_base` base url of Bilibili's live API, e.g., https://api.live.bilibili.com
+  /// * `room_id` real id of the room
+  pub async fn fetch(api_base: &str, room_id: i64) -> DanmujiResult<WsConfig> {
+    // api reference: https://github.com/lovelyyoshino/Bilibili-Live-API/blob/master/API.WebSocket.md
+    let cli = reqwest::ClientBuilder::new()
+      .user_agent(USER_AGENT)
+      .build()?;
+    let res = cli
+      .get(format!(
+        "{api_base}/xlive/web-room/v1/index/getDanmuInfo?id={room_id}&type=0"
+      ))
+      .header("referer", format!("https://live.bilibili.com/{room_id}"))
+      .send()
+      .await?;
+
+    let res: WsConfigResponse = res.json().await?;
+    if res.code != 0 {
+      return Err(DanmujiError::APIFormatError);
     }
+    Ok(res.data)
+  }
 
-    let mut res = String::new();
-    let index: usize = rand::thread_rng().gen_range(0..self.host_list.len());
-    let host = self.host_list.get(index).unwrap();
-    // build string
-    // ws://<host>:<port>/sub
-    res.push_str("ws://");
-    res.push_str(&host.host);
-    res.push(':');
-    res.push_str(&format!("{}", host.ws_port));
-    res.push_str("/sub");
-    Some(res)
+  /// plain websocket urls of all the hosts, see [WsConfig::rotated_urls]
+  pub fn ws_urls(&self) -> Vec<String> {
+    self.rotated_urls(WsHost::ws_url)
   }
 
-  #[allow(dead_code)]
-  pub fn get_wss_url(&self) -> Option<String> {
+  /// TLS websocket urls of all the hosts, see [WsConfig::rotated_urls]
+  pub fn wss_urls(&self) -> Vec<String> {
+    self.rotated_urls(WsHost::wss_url)
+  }
+
+  /// urls of all the hosts, starting from a random one so that clients
+  /// spread over the hosts, and then the rest in order to rotate through
+  /// when connecting fails
+  fn rotated_urls(&self, url: impl Fn(&WsHost) -> String) -> Vec<String> {
+    let Some(start) = self.random_index() else {
+      return vec![];
+    };
+    let len = self.host_list.len();
+    (0..len)
+      .map(|i| url(&self.host_list[(start + i) % len]))
+      .collect()
+  }
+
+  fn random_index(&self) -> Option<usize> {
     if self.host_list.is_empty() {
       return None;
     }
-
-    let mut res = String::new();
-    let index: usize = rand::thread_rng().gen_range(0..self.host_list.len());
-    let host = self.host_list.get(index).unwrap();
-    // build string
-    // ws://<host>:<port>/sub
-    res.push_str("wss://");
-    res.push_str(&host.host);
-    res.push(':');
-    res.push_str(&format!("{}", host.wss_port));
-    res.push_str("/sub");
-    Some(res)
+    Some(rand::thread_rng().gen_range(0..self.host_list.len()))
   }
 }
 
@@ -308,6 +391,18 @@ pub struct WsHost {
   pub ws_port: i64,
 }
 
+impl WsHost {
+  // ws://<host>:<port>/sub
+  pub fn ws_url(&self) -> String {
+    format!("ws://{}:{}/sub", self.host, self.ws_port)
+  }
+
+  // wss://<host>:<port>/sub
+  pub fn wss_url(&self) -> String {
+    format!("wss://{}:{}/sub", self.host, self.wss_port)
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BulletScreenConfig {
   pub bubble: i64,
@@ -327,7 +422,7 @@ pub struct BulletScreen {
 /// Utility Response types to make parsing easier
 #[derive(Debug, Serialize, Deserialize)]
 struct WsConfigResponse {
-  code: u8,
+  code: i32,
   message: String,
   ttl: u8,
   data: WsConfig,
@@ -369,3 +464,55 @@ impl BulletScreenConfig {
     Ok(res.data.property)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn room_config() -> RoomConfig {
+    serde_json::from_value(serde_json::json!({
+      "room_init": {
+        "room_id": 23058, "short_id": 3, "uid": 11153765, "need_p2p": 0,
+        "is_hidden": false, "is_locked": false, "is_portrait": false,
+        "live_status": 0, "hidden_till": 0, "lock_till": 0, "encrypted": false,
+        "pwd_verified": false, "live_time": 0, "room_shield": 0, "is_sp": 0,
+        "special_type": 0
+      },
+      // saved before the title was kept
+      "room": {
+        "roomid": "23058", "uid": "11153765", "content": "", "ctime": "",
+        "status": "0", "uname": "主播"
+      }
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn test_apply_room_events() {
+    let mut config = room_config();
+    assert_eq!("", config.room.title);
+
+    config.apply_event(&RoomEvent::Live {
+      live_time: Some(1650000000),
+    });
+    assert_eq!(1, config.room_init.live_status);
+    assert_eq!(1, config.room.live_status);
+    assert_eq!(1650000000, config.room_init.live_time);
+
+    config.apply_event(&RoomEvent::RoomChange {
+      title: "新标题".to_string(),
+      area_name: "唱见".to_string(),
+      parent_area_name: "娱乐".to_string(),
+    });
+    assert_eq!("新标题", config.room.title);
+
+    config.apply_event(&RoomEvent::Warning {
+      msg: "请调整直播内容".to_string(),
+    });
+    assert_eq!(1, config.room.live_status);
+
+    config.apply_event(&RoomEvent::Preparing);
+    assert_eq!(0, config.room_init.live_status);
+    assert_eq!(0, config.room.live_status);
+  }
+}
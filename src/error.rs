@@ -34,6 +34,42 @@ pub enum DanmujiError {
   /// Missing expected field in Bilibili's API response
   #[error("Unexpected Bilibili's API Format, Please File an Issue")]
   APIFormatError,
+
+  /// Websocket packet is shorter than the fixed header length
+  #[error("Truncated Websocket Header: only {0} bytes")]
+  TruncatedHeader(usize),
+
+  /// Packet length in the websocket header doesn't fit the
+  /// buffer it is read from
+  #[error("Invalid Websocket Packet Length: {packet_length}, buffer has {buffer_length} bytes")]
+  InvalidPacketLength {
+    packet_length: u32,
+    buffer_length: usize,
+  },
+
+  /// Fail to decompress a zlib or brotli compressed packet
+  #[error("Websocket Packet Decompression Error: {0}")]
+  Decompression(#[source] std::io::Error),
+
+  /// Websocket packet with an operation we don't know
+  #[error("Unknown Websocket Operation: {0}")]
+  UnknownOp(u32),
+
+  /// The recording writer has stopped, after failing to write
+  #[error("Recording Stopped")]
+  RecordingStopped,
+
+  /// Replay speed is neither realtime, instant nor a positive factor
+  #[error("Invalid Replay Speed: {0}")]
+  InvalidReplaySpeed(String),
+
+  /// No plugin registered under the name
+  #[error("Plugin Not Found: {0}")]
+  PluginNotFound(String),
+
+  /// Plugin config doesn't fit the plugin's schema, or is rejected by the plugin
+  #[error("Invalid Plugin Config: {0}")]
+  InvalidPluginConfig(String),
 }
 
 impl DanmujiError {
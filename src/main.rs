@@ -26,11 +26,14 @@ use axum::{
   routing::{get, get_service, post},
   Router,
 };
-use client::{BiliClient, BiliMessage};
+use client::{
+  Backoff, BiliClient, BiliMessage, ClientOptions, Heartbeat, RawPassthrough, ReplaySpeed,
+  PROTO_BROTLI,
+};
 pub(crate) use config::{RoomConfig, UserConfig};
 use error::DanmujiError;
 use hyper::StatusCode;
-use plugins::Chatbot;
+use plugins::{Chatbot, GiftThanker, GuardScThanker, PluginContext, PluginRegistry};
 use response::DanmujiApiResponse;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -38,7 +41,7 @@ use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio::sync::Mutex;
 use tower_http::services::{ServeDir, ServeFile};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 use tracing_subscriber::filter::targets::Targets;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -46,13 +49,16 @@ use tracing_subscriber::util::SubscriberInitExt;
 use apis::user::{getLoginStatus, getQrCode, loginCheck, logout};
 use sender::DanmujiSender;
 
-use apis::room::{disconnect, getRoomStatus, roomInit};
+use apis::plugins::{configurePlugin, disablePlugin, enablePlugin, listPlugins};
+use apis::room::{
+  disconnect, getDedupStats, getRoomLatency, getRoomPk, getRoomStatus, getUnhandledCommands,
+  roomInit,
+};
 use apis::settings::{queryGiftConfig, setGiftConfig};
+use apis::templates::{previewTemplate, validateTemplate};
 use apis::ws::handler;
 use util::*;
 
-use crate::plugins::GiftThanker;
-
 /// Result Type used by Danmuji
 pub type DanmujiResult<T> = std::result::Result<T, DanmujiError>;
 
@@ -84,10 +90,8 @@ pub struct DanmujiState {
   cli: BiliClient,
   // client that sends Bullet Screen Comments
   sender: DanmujiSender,
-  // thank gift component
-  thanker: GiftThanker,
-  // openai chatbot
-  chatbot: Chatbot,
+  // danmu processing plugins
+  plugins: PluginRegistry,
   // broadcast channel for subscription
   tx: broadcast::Sender<BiliMessage>,
   // sender for danmu to post
@@ -112,16 +116,59 @@ async fn main() {
 
   // setup broadcast channel & client
   let (tx, _rx) = broadcast::channel(100);
-  let mut cli = BiliClient::new(tx.clone());
+  // protocol version negotiated with Bilibili, brotli compression by default
+  let options = ClientOptions {
+    protover: std::env::var("DANMUJI_PROTOVER")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(PROTO_BROTLI),
+    backoff: backoff_from_env(),
+    heartbeat: heartbeat_from_env(),
+    // record raw frames of the connections if asked to
+    record_dir: std::env::var_os("DANMUJI_RECORD_DIR").map(PathBuf::from),
+    // forward unknown commands, "*" for all or a comma separated list
+    raw_passthrough: std::env::var("DANMUJI_RAW_CMDS")
+      .ok()
+      .and_then(|v| RawPassthrough::from_str(&v).ok())
+      .unwrap_or_default(),
+    dedup_window: dedup_window_from_env(),
+    // drop viewers entering the room, e.g. in busy rooms
+    forward_enters: std::env::var("DANMUJI_FORWARD_ENTERS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(true),
+    ..Default::default()
+  };
+  let mut cli = BiliClient::with_options(tx.clone(), options);
   // try to recover saved config
   let user = load_user_config();
   let room = load_room_config();
   info!("User: {:?}", user);
   info!("Room: {:?}", room);
-  // start connection if room config is set
-  if let Some(room) = &room {
+  // replay a recorded session in place of the live room if asked to,
+  // otherwise start connection if room config is set
+  if let Some(recording) = std::env::var_os("DANMUJI_REPLAY") {
+    let speed = match std::env::var("DANMUJI_REPLAY_SPEED").map(|v| v.parse::<ReplaySpeed>()) {
+      Ok(Ok(speed)) => speed,
+      Ok(Err(err)) => {
+        warn!("{}, Replaying in Realtime", err);
+        ReplaySpeed::Realtime
+      }
+      Err(_) => ReplaySpeed::Realtime,
+    };
+    let room_id = room.as_ref().map_or(0, |room| room.room_init.room_id);
+    if let Err(err) = cli.replay(room_id, &recording, speed) {
+      let path = std::path::Path::new(&recording);
+      error!("Fail Replaying {}: {}", path.display(), err);
+      std::process::exit(1);
+    }
+  } else if let Some(room) = &room {
     cli
-      .start(room.room_init.room_id, user.as_ref().map(|u| u.user.uid))
+      .start(
+        room.room_init.room_id,
+        user.as_ref().map(|u| u.user.uid),
+        room.transport,
+      )
       .unwrap();
   }
 
@@ -135,23 +182,34 @@ async fn main() {
     danmu_sender.connect_room(room.clone()).await.unwrap();
   }
 
-  // plugin: gift thanker
-  let gift_thank_config = load_thank_config();
-  let thanker = GiftThanker::start(gift_thank_config, tx.subscribe(), sender_tx.clone());
+  // plugins, the gift thanker starts from the legacy thank config
+  // until it has a plugin config of its own
+  let plugins = PluginRegistry::start(
+    vec![
+      Box::new(GiftThanker::new(load_thank_config())),
+      Box::<GuardScThanker>::default(),
+      Box::<Chatbot>::default(),
+    ],
+    tx.subscribe(),
+    PluginContext::new(sender_tx.clone()),
+    PLUGIN_CONFIG_DIR.as_path(),
+  );
 
-  let chatbot = Chatbot::start(tx.subscribe(), sender_tx.clone()).unwrap();
+  // keep the room's status up to date
+  let room_events = tx.subscribe();
 
   // initialize state
   let state = DanmujiState {
     cli,
     sender: danmu_sender,
-    thanker,
-    chatbot,
+    plugins,
     tx,
     sender_tx,
     user,
     room,
   };
+  let state = Arc::new(Mutex::new(state));
+  tokio::spawn(track_room_events(room_events, state.clone()));
 
   let assets = ServeDir::new(ASSETS_DIR.as_path());
 
@@ -163,14 +221,24 @@ async fn main() {
     .route("/api/logout", post(logout))
     .route("/api/ws", get(handler))
     .route("/api/roomStatus", get(getRoomStatus))
+    .route("/api/roomLatency", get(getRoomLatency))
+    .route("/api/unhandledCommands", get(getUnhandledCommands))
+    .route("/api/roomPk", get(getRoomPk))
+    .route("/api/dedupStats", get(getDedupStats))
     .route("/api/roomInit/:room_id", post(roomInit))
     .route("/api/disconnect", post(disconnect))
     .route("/api/getGiftConfig", get(queryGiftConfig))
     .route("/api/setGiftConfig", post(setGiftConfig))
+    .route("/api/plugins", get(listPlugins))
+    .route("/api/plugins/:name/enable", post(enablePlugin))
+    .route("/api/plugins/:name/disable", post(disablePlugin))
+    .route("/api/plugins/:name/config", post(configurePlugin))
+    .route("/api/templates/validate", post(validateTemplate))
+    .route("/api/templates/preview", post(previewTemplate))
     .fallback_service(
       get_service(ServeFile::new(INDEX_FILE.as_path())).handle_error(handle_error), // serve index page as fallback
     )
-    .layer(Extension(Arc::new(Mutex::new(state))));
+    .layer(Extension(state));
 
   axum::Server::bind(&"0.0.0.0:9000".parse().unwrap())
     .serve(app.into_make_service())
@@ -178,6 +246,69 @@ async fn main() {
     .unwrap();
 }
 
+/// apply the room events received to the room config of the state,
+/// so that the room status served needn't be fetched again
+async fn track_room_events(
+  mut upstream: broadcast::Receiver<BiliMessage>,
+  state: Arc<Mutex<DanmujiState>>,
+) {
+  loop {
+    let event = match upstream.recv().await {
+      Ok(BiliMessage::RoomEvent(event)) => event,
+      Ok(_) => continue,
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        warn!("Room Event Tracker Lagged, {} Messages Skipped", skipped);
+        continue;
+      }
+      Err(broadcast::error::RecvError::Closed) => break,
+    };
+    info!("Room Event: {:?}", event);
+    let mut state = state.lock().await;
+    if let Some(room) = state.room.as_mut() {
+      room.apply_event(&event);
+    }
+  }
+}
+
+/// reconnection policy, overridable with DANMUJI_BACKOFF_MAX_SECS and DANMUJI_MAX_RETRIES
+fn backoff_from_env() -> Backoff {
+  let default = Backoff::default();
+  Backoff {
+    max: std::env::var("DANMUJI_BACKOFF_MAX_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .map(std::time::Duration::from_secs)
+      .unwrap_or(default.max),
+    max_retries: std::env::var("DANMUJI_MAX_RETRIES")
+      .ok()
+      .and_then(|v| v.parse().ok()),
+    ..default
+  }
+}
+
+/// keepalive policy, overridable with DANMUJI_HEARTBEAT_TIMEOUT_SECS
+fn heartbeat_from_env() -> Heartbeat {
+  let default = Heartbeat::default();
+  Heartbeat {
+    timeout: std::env::var("DANMUJI_HEARTBEAT_TIMEOUT_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .map(std::time::Duration::from_secs)
+      .unwrap_or(default.timeout),
+    ..default
+  }
+}
+
+/// how long message ids are remembered to drop duplicates,
+/// overridable with DANMUJI_DEDUP_WINDOW_SECS, 0 to disable
+fn dedup_window_from_env() -> std::time::Duration {
+  std::env::var("DANMUJI_DEDUP_WINDOW_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .map(std::time::Duration::from_secs)
+    .unwrap_or(ClientOptions::default().dedup_window)
+}
+
 async fn handle_error(_err: impl std::error::Error) -> impl IntoResponse {
   (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong...")
 }
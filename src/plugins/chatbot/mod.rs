@@ -1,15 +1,16 @@
 mod context;
 
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use async_openai::{
+  config::OpenAIConfig,
+  types::{CreateCompletionRequest, Prompt},
 };
-
-use async_openai::types::Prompt;
-use tokio::sync::{broadcast::Receiver, mpsc::UnboundedSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tracing::error;
+use ts_rs::TS;
 
-use crate::{client::BiliMessage, DanmujiResult};
+use super::{parse_config, ConfigField, ConfigFieldKind, DanmujiPlugin, PluginContext};
+use crate::{client::BiliMessage, error::DanmujiError, DanmujiResult};
 
 use self::context::ChatbotMessageBuilder;
 
@@ -18,81 +19,120 @@ const MAX_COMPLETION_TOKEN: u16 = 1024;
 const MAX_MESSAGE_TOKEN: u16 = MAX_TOKEN - MAX_COMPLETION_TOKEN;
 const PERSIST_TO: &str = "gpt.data";
 
+/// Config of [Chatbot]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/ChatbotConfig.ts")]
+pub struct ChatbotConfig {
+  // danmu starting with the trigger are sent to the bot
+  trigger: String,
+  // openai completion model
+  model: String,
+}
+
+impl Default for ChatbotConfig {
+  fn default() -> Self {
+    Self {
+      trigger: "@bot ".to_string(),
+      model: "text-davinci-003".to_string(),
+    }
+  }
+}
+
+/// Answers danmu addressed to the bot with openai's completion
 #[derive(Debug)]
 pub struct Chatbot {
-  shutdown: Arc<AtomicBool>,
+  config: ChatbotConfig,
+  // conversation log, only present while the bot is started
+  context: Option<ChatbotMessageBuilder>,
+  client: async_openai::Client<OpenAIConfig>,
 }
 
 impl Chatbot {
-  pub fn start(
-    upstream: Receiver<BiliMessage>,
-    downstream: UnboundedSender<String>,
-  ) -> DanmujiResult<Self> {
-    let context = ChatbotMessageBuilder::new(MAX_MESSAGE_TOKEN, PERSIST_TO)?;
-    let bot = Self {
-      shutdown: Arc::new(AtomicBool::new(false)),
-    };
-
-    tokio::spawn(start_bot(
-      bot.shutdown.clone(),
-      upstream,
-      downstream,
-      context,
-    ));
+  pub const NAME: &'static str = "chatbot";
+}
 
-    Ok(bot)
+impl Default for Chatbot {
+  fn default() -> Self {
+    Self {
+      config: ChatbotConfig::default(),
+      context: None,
+      client: async_openai::Client::new(),
+    }
   }
 }
 
-impl Drop for Chatbot {
-  fn drop(&mut self) {
-    self.shutdown.store(true, Ordering::Relaxed);
+impl DanmujiPlugin for Chatbot {
+  fn name(&self) -> &'static str {
+    Self::NAME
   }
-}
 
-async fn start_bot(
-  shutdown: Arc<AtomicBool>,
-  mut upstream: Receiver<BiliMessage>,
-  downstream: UnboundedSender<String>,
-  mut _context: ChatbotMessageBuilder,
-) {
-  const IDENTIFIER: &str = "@bot ";
-  let client = async_openai::Client::new();
-  loop {
-    if shutdown.load(Ordering::Relaxed) {
-      break;
-    }
-    let msg = upstream.recv().await;
-    if let Err(err) = msg {
-      error!("BiliClient dropped: {}", err);
-      break;
-    };
-    let msg = msg.unwrap();
+  fn description(&self) -> &'static str {
+    "用OpenAI回答以触发词开头的弹幕"
+  }
+
+  fn config_schema(&self) -> Vec<ConfigField> {
+    vec![
+      ConfigField::new("trigger", ConfigFieldKind::String, "触发词"),
+      ConfigField::new("model", ConfigFieldKind::String, "OpenAI模型"),
+    ]
+  }
+
+  fn config(&self) -> Value {
+    serde_json::to_value(&self.config).unwrap_or_default()
+  }
+
+  fn start(&mut self, _ctx: &PluginContext) -> DanmujiResult<()> {
+    self.context = Some(ChatbotMessageBuilder::new(MAX_MESSAGE_TOKEN, PERSIST_TO)?);
+    Ok(())
+  }
+
+  fn on_message(&mut self, msg: &BiliMessage, ctx: &PluginContext) {
     let BiliMessage::Danmu(comment) = msg else {
-      continue;
+      return;
+    };
+    let Some(content) = comment.content().strip_prefix(&self.config.trigger) else {
+      return;
     };
-    let content = comment.content();
-    if let Some(content) = content.strip_prefix(IDENTIFIER) {
-      let request = async_openai::types::CreateCompletionRequestArgs::default()
-        .max_tokens(MAX_COMPLETION_TOKEN)
-        .model("text-davinci-003")
-        .prompt(Prompt::String(content.to_string()))
-        .build()
-        .unwrap();
-      let res = client.completions().create(request).await;
-      match res {
-        Ok(response) => {
-          for choice in response.choices {
-            if let Err(err) = downstream.send(choice.text) {
-              error!("Danmu Sender Dropped: {}", err);
-              break;
-            }
-          }
-        }
-        Err(err) => {
-          error!("{}", err);
-        }
+    let request = async_openai::types::CreateCompletionRequestArgs::default()
+      .max_tokens(MAX_COMPLETION_TOKEN)
+      .model(&self.config.model)
+      .prompt(Prompt::String(content.to_string()))
+      .build()
+      .unwrap();
+    // don't hold up other plugins while waiting for openai
+    tokio::spawn(complete(self.client.clone(), request, ctx.clone()));
+  }
+
+  fn on_config_change(&mut self, config: Value) -> DanmujiResult<()> {
+    let config: ChatbotConfig = parse_config(config)?;
+    if config.trigger.is_empty() {
+      return Err(DanmujiError::InvalidPluginConfig(
+        "Empty Chatbot Trigger".to_string(),
+      ));
+    }
+    self.config = config;
+    Ok(())
+  }
+
+  fn stop(&mut self) {
+    self.context = None;
+  }
+}
+
+async fn complete(
+  client: async_openai::Client<OpenAIConfig>,
+  request: CreateCompletionRequest,
+  ctx: PluginContext,
+) {
+  match client.completions().create(request).await {
+    Ok(response) => {
+      for choice in response.choices {
+        ctx.send_danmu(choice.text);
       }
     }
+    Err(err) => {
+      error!("{}", err);
+    }
   }
 }
@@ -0,0 +1,289 @@
+//! Delayed thanks: the gifts a user sends within a window are merged by name
+//! and thanked with one danmu, so that busy rooms aren't flooded with thanks.
+
+use std::{collections::VecDeque, time::Duration};
+
+use serde::Serialize;
+use tokio::{sync::mpsc::UnboundedReceiver, time::Instant};
+use tracing::{debug, error};
+
+use crate::{
+  client::GiftMessage,
+  plugins::{template::CompiledTemplate, PluginContext},
+};
+
+/// Gifts of the same name sent by a user within the window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GiftCount {
+  pub gift_name: String,
+  pub gift_num: u64,
+}
+
+/// Gifts sent by a user within the window, the context of the summary template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GiftSummary {
+  pub uid: u64,
+  pub uname: String,
+  // e.g. "2个小花花、5个辣条"
+  pub gifts: String,
+  pub counts: Vec<GiftCount>,
+}
+
+#[derive(Debug)]
+struct PendingThanks {
+  uid: u64,
+  uname: String,
+  // in the order the gifts are first sent
+  counts: Vec<GiftCount>,
+  // the window ends
+  deadline: Instant,
+}
+
+impl GiftSummary {
+  pub fn default_message() -> Self {
+    let counts = vec![
+      GiftCount {
+        gift_name: "小花花".to_string(),
+        gift_num: 1,
+      },
+      GiftCount {
+        gift_name: "辣条".to_string(),
+        gift_num: 5,
+      },
+    ];
+    PendingThanks {
+      uid: 0,
+      uname: "测试用户".to_string(),
+      counts,
+      deadline: Instant::now(),
+    }
+    .into()
+  }
+}
+
+impl From<PendingThanks> for GiftSummary {
+  fn from(pending: PendingThanks) -> Self {
+    let gifts = pending
+      .counts
+      .iter()
+      .map(|count| format!("{}个{}", count.gift_num, count.gift_name))
+      .collect::<Vec<_>>()
+      .join("、");
+    GiftSummary {
+      uid: pending.uid,
+      uname: pending.uname,
+      gifts,
+      counts: pending.counts,
+    }
+  }
+}
+
+/// Collects gifts per user. A user's window starts with their first gift,
+/// and at most `max_per_window` summaries are given out within any window,
+/// summaries over the cap are dropped.
+#[derive(Debug)]
+pub struct GiftAggregator {
+  window: Duration,
+  max_per_window: usize,
+  // ordered by deadline, as all windows have the same length
+  pending: VecDeque<PendingThanks>,
+  // when the summaries of the last window were given out
+  given: VecDeque<Instant>,
+}
+
+impl GiftAggregator {
+  pub fn new(window: Duration, max_per_window: usize) -> Self {
+    Self {
+      window,
+      max_per_window,
+      pending: VecDeque::new(),
+      given: VecDeque::new(),
+    }
+  }
+
+  pub fn add(&mut self, gift: &GiftMessage, now: Instant) {
+    let index = match self.pending.iter().position(|p| p.uid == *gift.uid()) {
+      Some(index) => index,
+      None => {
+        self.pending.push_back(PendingThanks {
+          uid: *gift.uid(),
+          uname: gift.uname().clone(),
+          counts: vec![],
+          deadline: now + self.window,
+        });
+        self.pending.len() - 1
+      }
+    };
+    let counts = &mut self.pending[index].counts;
+    match counts
+      .iter_mut()
+      .find(|count| count.gift_name == *gift.gift_name())
+    {
+      Some(count) => count.gift_num += gift.gift_num(),
+      None => counts.push(GiftCount {
+        gift_name: gift.gift_name().clone(),
+        gift_num: *gift.gift_num(),
+      }),
+    }
+  }
+
+  /// when the earliest window ends, None if no gift is pending
+  pub fn next_deadline(&self) -> Option<Instant> {
+    self.pending.front().map(|pending| pending.deadline)
+  }
+
+  /// summaries of the users whose window has ended
+  pub fn take_due(&mut self, now: Instant) -> Vec<GiftSummary> {
+    let due = self
+      .pending
+      .iter()
+      .take_while(|pending| pending.deadline <= now)
+      .count();
+    self.take(due, now)
+  }
+
+  /// summaries of all users, regardless of their windows
+  pub fn take_all(&mut self, now: Instant) -> Vec<GiftSummary> {
+    self.take(self.pending.len(), now)
+  }
+
+  fn take(&mut self, count: usize, now: Instant) -> Vec<GiftSummary> {
+    while let Some(given) = self.given.front() {
+      if now.duration_since(*given) < self.window {
+        break;
+      }
+      self.given.pop_front();
+    }
+
+    let mut summaries = vec![];
+    for pending in self.pending.drain(..count) {
+      if self.given.len() < self.max_per_window {
+        self.given.push_back(now);
+        summaries.push(pending.into());
+      } else {
+        debug!("Gift Thanks to {} Dropped, Too Many Thanks", pending.uname);
+      }
+    }
+    summaries
+  }
+}
+
+/// Thank the gifts from upstream with the summary template as their windows end.
+/// Pending thanks are given out right away once upstream is closed.
+pub async fn run_aggregator(
+  mut upstream: UnboundedReceiver<GiftMessage>,
+  mut aggregator: GiftAggregator,
+  summary: CompiledTemplate,
+  ctx: PluginContext,
+) {
+  loop {
+    let deadline = aggregator.next_deadline();
+    tokio::select! {
+      gift = upstream.recv() => match gift {
+        Some(gift) => aggregator.add(&gift, Instant::now()),
+        None => {
+          thank(&summary, aggregator.take_all(Instant::now()), &ctx);
+          break;
+        }
+      },
+      _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+        thank(&summary, aggregator.take_due(Instant::now()), &ctx);
+      }
+    }
+  }
+}
+
+fn thank(summary: &CompiledTemplate, summaries: Vec<GiftSummary>, ctx: &PluginContext) {
+  for s in summaries {
+    match summary.render(&s) {
+      Ok(reply) => ctx.send_danmu(reply),
+      Err(err) => error!("Fail Rendering Gift Summary: {}", err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+  use super::*;
+
+  const WINDOW: Duration = Duration::from_secs(10);
+
+  fn gift(uid: u64, uname: &str, gift_name: &str, gift_num: u64) -> GiftMessage {
+    let mut gift = serde_json::to_value(GiftMessage::default_message()).unwrap();
+    gift["uid"] = uid.into();
+    gift["uname"] = uname.into();
+    gift["gift_name"] = gift_name.into();
+    gift["gift_num"] = gift_num.into();
+    serde_json::from_value(gift).unwrap()
+  }
+
+  fn start(max_per_window: usize) -> (UnboundedSender<GiftMessage>, UnboundedReceiver<String>) {
+    let (gift_tx, gift_rx) = unbounded_channel();
+    let (sender_tx, sender_rx) = unbounded_channel();
+    tokio::spawn(run_aggregator(
+      gift_rx,
+      GiftAggregator::new(WINDOW, max_per_window),
+      CompiledTemplate::compile("感谢{uname}投喂的{gifts}~").unwrap(),
+      PluginContext::new(sender_tx),
+    ));
+    (gift_tx, sender_rx)
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_merge_gifts_per_user() {
+    let (gift_tx, mut sender_rx) = start(5);
+    gift_tx.send(gift(1, "甲", "小花花", 2)).unwrap();
+    // let the aggregator receive the gift before time moves on
+    tokio::task::yield_now().await;
+    tokio::time::advance(Duration::from_secs(3)).await;
+    gift_tx.send(gift(2, "乙", "辣条", 1)).unwrap();
+    gift_tx.send(gift(1, "甲", "辣条", 5)).unwrap();
+    gift_tx.send(gift(1, "甲", "小花花", 3)).unwrap();
+
+    // nothing is thanked before the window of the first gift ends
+    assert!(
+      tokio::time::timeout(Duration::from_secs(6), sender_rx.recv())
+        .await
+        .is_err()
+    );
+    assert_eq!(
+      "感谢甲投喂的5个小花花、5个辣条~",
+      sender_rx.recv().await.unwrap()
+    );
+    let first = Instant::now();
+    assert_eq!("感谢乙投喂的1个辣条~", sender_rx.recv().await.unwrap());
+    // the second user's window started 3 seconds later
+    assert_eq!(Duration::from_secs(3), Instant::now() - first);
+
+    // a gift after the window starts a new one
+    gift_tx.send(gift(1, "甲", "小花花", 1)).unwrap();
+    assert_eq!("感谢甲投喂的1个小花花~", sender_rx.recv().await.unwrap());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_cap_per_window() {
+    let (gift_tx, mut sender_rx) = start(2);
+    for uid in 0..4 {
+      gift_tx
+        .send(gift(uid, &uid.to_string(), "辣条", 1))
+        .unwrap();
+    }
+    assert_eq!("感谢0投喂的1个辣条~", sender_rx.recv().await.unwrap());
+    assert_eq!("感谢1投喂的1个辣条~", sender_rx.recv().await.unwrap());
+
+    // thanks can be given again once the window has passed
+    tokio::time::sleep(WINDOW).await;
+    gift_tx.send(gift(9, "9", "辣条", 1)).unwrap();
+    // closing upstream thanks the pending gift right away
+    drop(gift_tx);
+
+    let mut thanks = vec![];
+    while let Some(reply) = sender_rx.recv().await {
+      thanks.push(reply);
+    }
+    // thanks to 2 and 3 are over the cap
+    assert_eq!(vec!["感谢9投喂的1个辣条~"], thanks);
+  }
+}
@@ -0,0 +1,352 @@
+mod aggregator;
+mod rules;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tracing::{error, warn};
+use ts_rs::TS;
+
+pub use self::aggregator::GiftSummary;
+use self::aggregator::{run_aggregator, GiftAggregator};
+use self::rules::{ThankAction, ThankRule};
+use super::{
+  parse_config, template::CompiledTemplate, ConfigField, ConfigFieldKind, DanmujiPlugin,
+  PluginContext,
+};
+use crate::{
+  client::{BiliMessage, GiftMessage},
+  error::DanmujiError,
+  DanmujiResult,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(default)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/GiftThankConfig.ts")]
+pub struct GiftThankConfig {
+  // reply template
+  template: String,
+  // open or closed
+  open: bool,
+  // thank the gifts of a user together after a delay, instead of one by one
+  aggregate: bool,
+  // how long the gifts of a user are collected, in seconds
+  aggregate_window_secs: u32,
+  // most aggregated thanks sent within a window
+  max_thanks_per_window: u32,
+  // template of aggregated thanks
+  summary_template: String,
+  // tried in order before the default template,
+  // gifts matched by a rule are thanked right away even in aggregate mode
+  rules: Vec<ThankRule>,
+}
+
+impl Default for GiftThankConfig {
+  fn default() -> Self {
+    Self {
+      template: "感谢{uname}投喂的{gift_num}个{gift_name}~".to_string(),
+      open: true,
+      aggregate: false,
+      aggregate_window_secs: 5,
+      max_thanks_per_window: 3,
+      summary_template: "感谢{uname}投喂的{gifts}~".to_string(),
+      rules: vec![],
+    }
+  }
+}
+
+impl GiftThankConfig {
+  /// compile the templates, which also checks the aggregation settings make sense
+  fn compile(&self) -> DanmujiResult<ThankTemplates> {
+    let invalid = |msg: String| DanmujiError::InvalidPluginConfig(msg);
+    if self.aggregate_window_secs == 0 || self.max_thanks_per_window == 0 {
+      return Err(invalid(
+        "Aggregate Window and Max Thanks Should Be Positive".to_string(),
+      ));
+    }
+
+    let default = CompiledTemplate::compile(&self.template)
+      .map_err(|err| invalid(format!("Thank Template: {}", err)))?;
+    let summary = CompiledTemplate::compile(&self.summary_template)
+      .map_err(|err| invalid(format!("Summary Template: {}", err)))?;
+    let rules = self
+      .rules
+      .iter()
+      .enumerate()
+      .map(|(i, rule)| match &rule.action {
+        ThankAction::Thank(template) => CompiledTemplate::compile(template)
+          .map(Some)
+          .map_err(|err| invalid(format!("Rule {}: {}", i + 1, err))),
+        ThankAction::Silent => Ok(None),
+      })
+      .collect::<DanmujiResult<_>>()?;
+    Ok(ThankTemplates {
+      default,
+      summary,
+      rules,
+    })
+  }
+}
+
+/// Compiled templates of a [GiftThankConfig]
+#[derive(Debug)]
+struct ThankTemplates {
+  default: CompiledTemplate,
+  summary: CompiledTemplate,
+  // one for each rule, None for silent rules
+  rules: Vec<Option<CompiledTemplate>>,
+}
+
+/// Thanks the gifts received with danmu rendered from [GiftThankConfig],
+/// one by one or aggregated per user
+#[derive(Debug)]
+pub struct GiftThanker {
+  config: GiftThankConfig,
+  // compiled from config
+  templates: ThankTemplates,
+  // set while started
+  ctx: Option<PluginContext>,
+  // gifts to the aggregation task, set while started in aggregate mode
+  aggregator: Option<UnboundedSender<GiftMessage>>,
+  // the room last connected to, which rules on medals refer to
+  room_id: Option<i64>,
+}
+
+impl GiftThanker {
+  pub const NAME: &'static str = "gift_thanker";
+
+  /// the default config is used in place of an invalid one
+  pub fn new(config: GiftThankConfig) -> Self {
+    let (config, templates) = match config.compile() {
+      Ok(templates) => (config, templates),
+      Err(err) => {
+        warn!("Invalid Gift Thank Config, Using the Default: {}", err);
+        let config = GiftThankConfig::default();
+        let templates = config.compile().expect("Default Config Is Valid");
+        (config, templates)
+      }
+    };
+    Self {
+      config,
+      templates,
+      ctx: None,
+      aggregator: None,
+      room_id: None,
+    }
+  }
+
+  /// thank message of the gift rendered right away, None if it shouldn't
+  /// be thanked or isn't a gift
+  pub fn get_thank_message(&self, msg: &BiliMessage) -> Option<String> {
+    let BiliMessage::Gift(gift) = msg else {
+      return None;
+    };
+    if !self.config.open {
+      return None;
+    }
+    let template = match self.matched_rule(gift) {
+      Some(i) => self.templates.rules[i].as_ref()?,
+      None => &self.templates.default,
+    };
+    template
+      .render(gift)
+      .map_err(|err| error!("Fail Rendering Gift Thanks: {}", err))
+      .ok()
+  }
+
+  // index of the first rule matching the gift
+  fn matched_rule(&self, gift: &GiftMessage) -> Option<usize> {
+    self
+      .config
+      .rules
+      .iter()
+      .position(|rule| rule.matches(gift, self.room_id))
+  }
+
+  // dropping the sender lets the old task thank its pending gifts and exit
+  fn restart_aggregator(&mut self) {
+    self.aggregator = None;
+    let Some(ctx) = self.ctx.as_ref().filter(|_| self.config.aggregate) else {
+      return;
+    };
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(run_aggregator(
+      rx,
+      GiftAggregator::new(
+        Duration::from_secs(self.config.aggregate_window_secs as u64),
+        self.config.max_thanks_per_window as usize,
+      ),
+      self.templates.summary.clone(),
+      ctx.clone(),
+    ));
+    self.aggregator = Some(tx);
+  }
+}
+
+impl DanmujiPlugin for GiftThanker {
+  fn name(&self) -> &'static str {
+    Self::NAME
+  }
+
+  fn description(&self) -> &'static str {
+    "感谢投喂礼物的观众"
+  }
+
+  fn config_schema(&self) -> Vec<ConfigField> {
+    vec![
+      ConfigField::new(
+        "template",
+        ConfigFieldKind::Template,
+        "感谢弹幕模板, 可用变量: {uname} {gift_num} {gift_name}",
+      ),
+      ConfigField::new("open", ConfigFieldKind::Bool, "是否开启礼物感谢"),
+      ConfigField::new("aggregate", ConfigFieldKind::Bool, "是否延时汇总感谢"),
+      ConfigField::new(
+        "aggregate_window_secs",
+        ConfigFieldKind::Number,
+        "汇总同一用户礼物的时长, 单位秒",
+      ),
+      ConfigField::new(
+        "max_thanks_per_window",
+        ConfigFieldKind::Number,
+        "每个时段内最多发送的汇总感谢数",
+      ),
+      ConfigField::new(
+        "summary_template",
+        ConfigFieldKind::Template,
+        "汇总感谢弹幕模板, 可用变量: {uname} {gifts}",
+      ),
+      ConfigField::new(
+        "rules",
+        ConfigFieldKind::Rules,
+        "按顺序匹配的感谢规则, 匹配的礼物使用规则的模板或不感谢",
+      ),
+    ]
+  }
+
+  fn config(&self) -> Value {
+    serde_json::to_value(&self.config).unwrap_or_default()
+  }
+
+  fn start(&mut self, ctx: &PluginContext) -> DanmujiResult<()> {
+    self.ctx = Some(ctx.clone());
+    self.restart_aggregator();
+    Ok(())
+  }
+
+  fn on_message(&mut self, msg: &BiliMessage, ctx: &PluginContext) {
+    match (&self.aggregator, msg) {
+      (_, BiliMessage::Connected { room_id, .. }) => self.room_id = Some(*room_id),
+      (Some(aggregator), BiliMessage::Gift(gift)) if self.matched_rule(gift).is_none() => {
+        if self.config.open {
+          let _ = aggregator.send(gift.clone());
+        }
+      }
+      _ => {
+        if let Some(reply) = self.get_thank_message(msg) {
+          ctx.send_danmu(reply);
+        }
+      }
+    }
+  }
+
+  fn on_config_change(&mut self, config: Value) -> DanmujiResult<()> {
+    let config: GiftThankConfig = parse_config(config)?;
+    self.templates = config.compile()?;
+    self.config = config;
+    self.restart_aggregator();
+    Ok(())
+  }
+
+  fn stop(&mut self) {
+    self.ctx = None;
+    self.restart_aggregator();
+  }
+}
+
+impl Default for GiftThanker {
+  fn default() -> Self {
+    Self::new(GiftThankConfig::default())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::client::GiftMessage;
+
+  #[tokio::test]
+  async fn test_template_basics() {
+    let test_msg = BiliMessage::Gift(GiftMessage::default_message());
+    let thanker = GiftThanker::default();
+    assert_eq!(
+      Some("感谢测试用户投喂的1个小花花~".to_string()),
+      thanker.get_thank_message(&test_msg)
+    );
+  }
+
+  #[test]
+  fn test_rules() {
+    let config: GiftThankConfig = serde_json::from_value(serde_json::json!({
+      "rules": [
+        { "coin_type": "Silver", "action": { "kind": "Silent" } },
+        { "min_value": 100, "action": { "kind": "Thank", "template": "谢谢{uname}老板的{gift_name}!" } },
+        { "gift_name": "小花花", "action": { "kind": "Thank", "template": "{uname}的小花花收到啦" } },
+        // never reached for 小花花
+        { "gift_id": 0, "action": { "kind": "Silent" } },
+      ]
+    }))
+    .unwrap();
+    let thanker = GiftThanker::new(config);
+    let thank = |gift: serde_json::Value| {
+      let mut msg = serde_json::to_value(GiftMessage::default_message()).unwrap();
+      msg
+        .as_object_mut()
+        .unwrap()
+        .extend(gift.as_object().unwrap().clone());
+      thanker.get_thank_message(&BiliMessage::Gift(serde_json::from_value(msg).unwrap()))
+    };
+
+    assert_eq!(None, thank(serde_json::json!({ "coin_type": "Silver" })));
+    assert_eq!(
+      Some("谢谢测试用户老板的小花花!".to_string()),
+      thank(serde_json::json!({ "total_coin": 100000 }))
+    );
+    assert_eq!(
+      Some("测试用户的小花花收到啦".to_string()),
+      thank(serde_json::json!({}))
+    );
+    // no rule matches
+    assert_eq!(
+      Some("感谢测试用户投喂的1个辣条~".to_string()),
+      thank(serde_json::json!({ "gift_id": 1, "gift_name": "辣条" }))
+    );
+  }
+
+  #[test]
+  fn test_reject_invalid_rule_template() {
+    let mut thanker = GiftThanker::default();
+    let invalid = serde_json::json!({
+      "rules": [
+        { "coin_type": "Silver", "action": { "kind": "Silent" } },
+        { "gift_id": 1, "action": { "kind": "Thank", "template": "{uname" } },
+      ]
+    });
+    let err = thanker.on_config_change(invalid).unwrap_err();
+    assert!(err.to_string().contains("Rule 2"));
+  }
+
+  #[test]
+  fn test_reject_invalid_template() {
+    let mut thanker = GiftThanker::default();
+    let invalid = serde_json::json!({ "template": "感谢{uname", "open": true });
+    assert!(thanker.on_config_change(invalid).is_err());
+    assert_eq!(
+      serde_json::to_value(GiftThankConfig::default()).unwrap(),
+      thanker.config()
+    );
+  }
+}
@@ -0,0 +1,177 @@
+//! Rules picking how a gift is thanked, e.g., special lines for expensive
+//! gifts or silence for free ones. Rules are tried in order and the first
+//! matching one decides; gifts no rule matches get the default template.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::client::{CoinType, GiftMessage, GuardType};
+
+/// A rule matches a gift when all of its conditions set hold,
+/// a rule without conditions matches every gift
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/ThankRule.ts")]
+pub struct ThankRule {
+  #[ts(type = "number | null")]
+  pub gift_id: Option<u64>,
+  pub gift_name: Option<String>,
+  pub coin_type: Option<CoinType>,
+  // at least this many gifts sent at once
+  #[ts(type = "number | null")]
+  pub min_num: Option<u64>,
+  // the gifts are worth at least this much CNY
+  pub min_value: Option<f64>,
+  // the sender is of this guard level or above
+  pub min_guard: Option<GuardType>,
+  // whether the sender wears the medal of the connected room,
+  // medals of other rooms don't count
+  pub has_room_medal: Option<bool>,
+  pub action: ThankAction,
+}
+
+/// What to do with a gift matched by a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "kind", content = "template")]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/ThankAction.ts")]
+pub enum ThankAction {
+  /// thank with the template in place of the default one
+  Thank(String),
+  /// don't thank the gift
+  Silent,
+}
+
+impl ThankRule {
+  /// whether the gift sent to the room matches, the room being None
+  /// before the client connects
+  pub fn matches(&self, gift: &GiftMessage, room_id: Option<i64>) -> bool {
+    self.gift_id.is_none_or(|id| id == *gift.gift_id())
+      && self
+        .gift_name
+        .as_ref()
+        .is_none_or(|name| name == gift.gift_name())
+      && self.coin_type.is_none_or(|coin| coin == *gift.coin_type())
+      && self.min_num.is_none_or(|num| *gift.gift_num() >= num)
+      && self
+        .min_value
+        .is_none_or(|value| gift.total_value_in_cny() >= value)
+      && self.min_guard.is_none_or(|guard| *gift.guard() >= guard)
+      && self
+        .has_room_medal
+        .is_none_or(|has_room_medal| has_medal_of(gift, room_id) == has_room_medal)
+  }
+}
+
+// the medal's room is the real room id, which is what the client connects to
+fn has_medal_of(gift: &GiftMessage, room_id: Option<i64>) -> bool {
+  match (gift.medal(), room_id) {
+    (Some(medal), Some(room_id)) => *medal.streamer_roomid() as i64 == room_id,
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::client::DanmuMessage;
+
+  fn rule() -> ThankRule {
+    ThankRule {
+      gift_id: None,
+      gift_name: None,
+      coin_type: None,
+      min_num: None,
+      min_value: None,
+      min_guard: None,
+      has_room_medal: None,
+      action: ThankAction::Silent,
+    }
+  }
+
+  #[test]
+  fn test_rule_matches() {
+    // 1 小花花 worth 0.1 CNY, from a captain without medal
+    let gift = GiftMessage::default_message();
+    assert!(rule().matches(&gift, Some(12345)));
+
+    let matching = [
+      ThankRule {
+        gift_name: Some("小花花".to_string()),
+        min_guard: Some(GuardType::Captain),
+        ..rule()
+      },
+      ThankRule {
+        coin_type: Some(CoinType::Gold),
+        min_value: Some(0.1),
+        has_room_medal: Some(false),
+        ..rule()
+      },
+    ];
+    assert!(matching.iter().all(|rule| rule.matches(&gift, Some(12345))));
+
+    let not_matching = [
+      ThankRule {
+        gift_id: Some(1),
+        ..rule()
+      },
+      ThankRule {
+        min_num: Some(2),
+        ..rule()
+      },
+      ThankRule {
+        min_value: Some(1.0),
+        ..rule()
+      },
+      ThankRule {
+        min_guard: Some(GuardType::Admiral),
+        ..rule()
+      },
+      ThankRule {
+        has_room_medal: Some(true),
+        ..rule()
+      },
+    ];
+    assert!(not_matching
+      .iter()
+      .all(|rule| !rule.matches(&gift, Some(12345))));
+  }
+
+  #[test]
+  fn test_room_medal() {
+    let rule = ThankRule {
+      has_room_medal: Some(true),
+      ..rule()
+    };
+    let mut gift = serde_json::to_value(GiftMessage::default_message()).unwrap();
+    gift["medal"] = serde_json::to_value(DanmuMessage::default_message()).unwrap()["medal"].clone();
+    gift["medal"]["streamer_roomid"] = 12345.into();
+    let gift: GiftMessage = serde_json::from_value(gift).unwrap();
+    assert!(rule.matches(&gift, Some(12345)));
+    // a medal of another room, or before the client connects
+    assert!(!rule.matches(&gift, Some(54321)));
+    assert!(!rule.matches(&gift, None));
+  }
+
+  #[test]
+  fn test_action_format() {
+    let rule: ThankRule = serde_json::from_value(serde_json::json!({
+      "coin_type": "Silver",
+      "action": { "kind": "Silent" }
+    }))
+    .unwrap();
+    assert_eq!(Some(CoinType::Silver), rule.coin_type);
+    assert_eq!(ThankAction::Silent, rule.action);
+
+    let rule: ThankRule = serde_json::from_value(serde_json::json!({
+      "min_value": 100,
+      "action": { "kind": "Thank", "template": "谢谢{uname}老板!" }
+    }))
+    .unwrap();
+    assert_eq!(Some(100.0), rule.min_value);
+    assert_eq!(
+      ThankAction::Thank("谢谢{uname}老板!".to_string()),
+      rule.action
+    );
+  }
+}
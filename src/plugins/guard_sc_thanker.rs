@@ -0,0 +1,288 @@
+//! Thanks guard purchases (上舰) and super chats (醒目留言),
+//! which streamers most want acknowledged.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+use ts_rs::TS;
+
+use super::{
+  parse_config, template::CompiledTemplate, ConfigField, ConfigFieldKind, DanmujiPlugin,
+  PluginContext,
+};
+use crate::{
+  client::{BiliMessage, GuardPurchaseMessage, GuardType, SuperChatMessage},
+  error::DanmujiError,
+  DanmujiResult,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(default)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/GuardScThankConfig.ts")]
+pub struct GuardScThankConfig {
+  // thank guard purchases or not
+  guard_open: bool,
+  guard_template: String,
+  // thank super chats or not
+  super_chat_open: bool,
+  super_chat_template: String,
+  // super chats longer than this many characters are cut short in the thanks
+  excerpt_len: u32,
+}
+
+impl Default for GuardScThankConfig {
+  fn default() -> Self {
+    Self {
+      guard_open: true,
+      guard_template: "感谢{uname}{action}了{months}个月{guard_name}~".to_string(),
+      super_chat_open: true,
+      super_chat_template: "感谢{uname}的{price}元醒目留言: {excerpt}".to_string(),
+      excerpt_len: 10,
+    }
+  }
+}
+
+impl GuardScThankConfig {
+  fn compile(&self) -> DanmujiResult<GuardScTemplates> {
+    let invalid = |msg: String| DanmujiError::InvalidPluginConfig(msg);
+    if self.excerpt_len == 0 {
+      return Err(invalid("Excerpt Length Should Be Positive".to_string()));
+    }
+    Ok(GuardScTemplates {
+      guard: CompiledTemplate::compile(&self.guard_template)
+        .map_err(|err| invalid(format!("Guard Template: {}", err)))?,
+      super_chat: CompiledTemplate::compile(&self.super_chat_template)
+        .map_err(|err| invalid(format!("Super Chat Template: {}", err)))?,
+    })
+  }
+}
+
+/// Compiled templates of a [GuardScThankConfig]
+#[derive(Debug)]
+struct GuardScTemplates {
+  guard: CompiledTemplate,
+  super_chat: CompiledTemplate,
+}
+
+/// What guard purchase templates are rendered with,
+/// the fields of the purchase plus readable names
+#[derive(Debug, Serialize)]
+pub struct GuardThanks<'a> {
+  #[serde(flatten)]
+  purchase: &'a GuardPurchaseMessage,
+  // 舰长, 提督 or 总督
+  guard_name: &'static str,
+  // 开通 or 续费
+  action: &'static str,
+}
+
+impl<'a> GuardThanks<'a> {
+  pub fn new(purchase: &'a GuardPurchaseMessage) -> Self {
+    Self {
+      purchase,
+      guard_name: match purchase.guard() {
+        GuardType::Governor => "总督",
+        GuardType::Admiral => "提督",
+        GuardType::Captain | GuardType::NoGuard => "舰长",
+      },
+      action: if *purchase.is_renewal() {
+        "续费"
+      } else {
+        "开通"
+      },
+    }
+  }
+}
+
+/// What super chat templates are rendered with,
+/// the fields of the super chat plus an excerpt of its message
+#[derive(Debug, Serialize)]
+pub struct SuperChatThanks<'a> {
+  #[serde(flatten)]
+  super_chat: &'a SuperChatMessage,
+  excerpt: String,
+}
+
+impl<'a> SuperChatThanks<'a> {
+  pub fn new(super_chat: &'a SuperChatMessage, excerpt_len: usize) -> Self {
+    let message = super_chat.message();
+    let excerpt = match message.char_indices().nth(excerpt_len) {
+      Some((end, _)) => format!("{}…", &message[..end]),
+      None => message.clone(),
+    };
+    Self {
+      super_chat,
+      excerpt,
+    }
+  }
+}
+
+/// Thanks guard purchases and super chats with danmu rendered
+/// from [GuardScThankConfig]
+#[derive(Debug)]
+pub struct GuardScThanker {
+  config: GuardScThankConfig,
+  // compiled from config
+  templates: GuardScTemplates,
+}
+
+impl GuardScThanker {
+  pub const NAME: &'static str = "guard_sc_thanker";
+
+  /// thank message of a guard purchase or super chat, None for other
+  /// messages or if thanking them is turned off
+  pub fn get_thank_message(&self, msg: &BiliMessage) -> Option<String> {
+    let rendered = match msg {
+      BiliMessage::GuardPurchase(purchase) if self.config.guard_open => {
+        self.templates.guard.render(&GuardThanks::new(purchase))
+      }
+      BiliMessage::SuperChat(super_chat) if self.config.super_chat_open => {
+        let thanks = SuperChatThanks::new(super_chat, self.config.excerpt_len as usize);
+        self.templates.super_chat.render(&thanks)
+      }
+      _ => return None,
+    };
+    rendered
+      .map_err(|err| error!("Fail Rendering Guard/Super Chat Thanks: {}", err))
+      .ok()
+  }
+}
+
+impl Default for GuardScThanker {
+  fn default() -> Self {
+    let config = GuardScThankConfig::default();
+    let templates = config.compile().expect("Default Config Is Valid");
+    Self { config, templates }
+  }
+}
+
+impl DanmujiPlugin for GuardScThanker {
+  fn name(&self) -> &'static str {
+    Self::NAME
+  }
+
+  fn description(&self) -> &'static str {
+    "感谢上舰和醒目留言"
+  }
+
+  fn config_schema(&self) -> Vec<ConfigField> {
+    vec![
+      ConfigField::new("guard_open", ConfigFieldKind::Bool, "是否感谢上舰"),
+      ConfigField::new(
+        "guard_template",
+        ConfigFieldKind::Template,
+        "上舰感谢弹幕模板, 可用变量: {uname} {action} {months} {guard_name} {price}",
+      ),
+      ConfigField::new("super_chat_open", ConfigFieldKind::Bool, "是否感谢醒目留言"),
+      ConfigField::new(
+        "super_chat_template",
+        ConfigFieldKind::Template,
+        "醒目留言感谢弹幕模板, 可用变量: {uname} {price} {message} {excerpt}",
+      ),
+      ConfigField::new(
+        "excerpt_len",
+        ConfigFieldKind::Number,
+        "醒目留言摘要{excerpt}的最大字数",
+      ),
+    ]
+  }
+
+  fn config(&self) -> Value {
+    serde_json::to_value(&self.config).unwrap_or_default()
+  }
+
+  fn start(&mut self, _ctx: &PluginContext) -> DanmujiResult<()> {
+    Ok(())
+  }
+
+  fn on_message(&mut self, msg: &BiliMessage, ctx: &PluginContext) {
+    if let Some(reply) = self.get_thank_message(msg) {
+      ctx.send_danmu(reply);
+    }
+  }
+
+  fn on_config_change(&mut self, config: Value) -> DanmujiResult<()> {
+    let config: GuardScThankConfig = parse_config(config)?;
+    self.templates = config.compile()?;
+    self.config = config;
+    Ok(())
+  }
+
+  fn stop(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn thank(thanker: &GuardScThanker, msg: BiliMessage) -> Option<String> {
+    thanker.get_thank_message(&msg)
+  }
+
+  #[test]
+  fn test_thank_guard_purchase() {
+    let thanker = GuardScThanker::default();
+    let purchase = GuardPurchaseMessage::default_message();
+    assert_eq!(
+      Some("感谢测试用户开通了1个月舰长~".to_string()),
+      thank(&thanker, BiliMessage::GuardPurchase(purchase))
+    );
+
+    let mut purchase = serde_json::to_value(GuardPurchaseMessage::default_message()).unwrap();
+    purchase["guard"] = json!("Admiral");
+    purchase["months"] = json!(3);
+    purchase["is_renewal"] = json!(true);
+    let purchase = serde_json::from_value(purchase).unwrap();
+    assert_eq!(
+      Some("感谢测试用户续费了3个月提督~".to_string()),
+      thank(&thanker, BiliMessage::GuardPurchase(purchase))
+    );
+  }
+
+  #[test]
+  fn test_thank_super_chat_excerpt() {
+    let mut thanker = GuardScThanker::default();
+    let super_chat = || BiliMessage::SuperChat(SuperChatMessage::default_message());
+    assert_eq!(
+      Some("感谢测试用户的30元醒目留言: 主播今天唱的歌太好听…".to_string()),
+      thank(&thanker, super_chat())
+    );
+
+    // short messages are kept whole
+    let mut config = thanker.config();
+    config["excerpt_len"] = json!(100);
+    thanker.on_config_change(config).unwrap();
+    assert_eq!(
+      Some("感谢测试用户的30元醒目留言: 主播今天唱的歌太好听了, 明天还来!".to_string()),
+      thank(&thanker, super_chat())
+    );
+  }
+
+  #[test]
+  fn test_config_flags_and_validation() {
+    let mut thanker = GuardScThanker::default();
+    thanker
+      .on_config_change(json!({ "super_chat_open": false }))
+      .unwrap();
+    assert!(thank(
+      &thanker,
+      BiliMessage::SuperChat(SuperChatMessage::default_message())
+    )
+    .is_none());
+    assert!(thank(
+      &thanker,
+      BiliMessage::GuardPurchase(GuardPurchaseMessage::default_message())
+    )
+    .is_some());
+
+    let err = thanker
+      .on_config_change(json!({ "guard_template": "感谢{uname" }))
+      .unwrap_err();
+    assert!(err.to_string().contains("Guard Template"));
+    // the rejected config isn't applied
+    assert_eq!(json!(false), thanker.config()["super_chat_open"]);
+  }
+}
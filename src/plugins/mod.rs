@@ -1,4 +1,114 @@
+//! Plugins process the messages received from Bilibili, e.g., thank gifts,
+//! and may reply by posting danmu. All plugins are owned by a [PluginRegistry],
+//! which feeds them messages, turns them on and off and keeps their configs.
+
 mod chatbot;
 mod gift_thanker;
+mod guard_sc_thanker;
+mod registry;
+pub mod template;
+
 pub use chatbot::Chatbot;
 pub use gift_thanker::{GiftThankConfig, GiftThanker};
+pub use guard_sc_thanker::GuardScThanker;
+pub use registry::{PluginInfo, PluginRegistry};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::error;
+use ts_rs::TS;
+
+use crate::{client::BiliMessage, error::DanmujiError, DanmujiResult};
+
+/// A danmu processing plugin.
+///
+/// Lifecycle: start -> (on_message | on_config_change)* -> stop -> start ...
+/// The config can be changed while the plugin is stopped as well.
+/// Hooks are called from the registry's dispatch loop and shouldn't block,
+/// plugins doing I/O spawn their own tasks.
+pub trait DanmujiPlugin: Send + std::fmt::Debug {
+  /// unique name, identifies the plugin in the API and names its config file
+  fn name(&self) -> &'static str;
+
+  /// what the plugin does, shown to the user
+  fn description(&self) -> &'static str;
+
+  /// fields of the plugin's config
+  fn config_schema(&self) -> Vec<ConfigField>;
+
+  /// current config as JSON
+  fn config(&self) -> Value;
+
+  /// called when the plugin is enabled
+  fn start(&mut self, ctx: &PluginContext) -> DanmujiResult<()>;
+
+  /// called with every message received while the plugin is enabled
+  fn on_message(&mut self, msg: &BiliMessage, ctx: &PluginContext);
+
+  /// validate and apply a new config, the current one is kept on error
+  fn on_config_change(&mut self, config: Value) -> DanmujiResult<()>;
+
+  /// called when the plugin is disabled
+  fn stop(&mut self);
+}
+
+/// What a plugin can do to the outside world
+#[derive(Debug, Clone)]
+pub struct PluginContext {
+  // danmu to be posted by [DanmujiSender]
+  sender: UnboundedSender<String>,
+}
+
+impl PluginContext {
+  pub fn new(sender: UnboundedSender<String>) -> Self {
+    Self { sender }
+  }
+
+  /// post a danmu to the connected room
+  pub fn send_danmu(&self, danmu: String) {
+    if let Err(err) = self.sender.send(danmu) {
+      error!("Danmu Sender Dropped: {}", err);
+    }
+  }
+}
+
+/// A field of a plugin's config, from which the frontend builds the config form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/ConfigField.ts")]
+pub struct ConfigField {
+  // key of the field in the JSON config
+  name: String,
+  kind: ConfigFieldKind,
+  description: String,
+}
+
+impl ConfigField {
+  pub fn new(name: &str, kind: ConfigFieldKind, description: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      kind,
+      description: description.to_string(),
+    }
+  }
+}
+
+/// Type of a config field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/ConfigFieldKind.ts")]
+pub enum ConfigFieldKind {
+  Bool,
+  Number,
+  String,
+  /// a TinyTemplate string, e.g., "感谢{uname}"
+  Template,
+  /// an ordered list of gift thank rules
+  Rules,
+}
+
+/// parse the JSON config of a plugin into its typed config
+pub fn parse_config<T: DeserializeOwned>(config: Value) -> DanmujiResult<T> {
+  serde_json::from_value(config).map_err(|err| DanmujiError::InvalidPluginConfig(err.to_string()))
+}
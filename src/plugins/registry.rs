@@ -0,0 +1,387 @@
+//! The registry owns all plugins. It feeds them the messages received from
+//! [crate::client::BiliClient] in one task, turns them on and off and persists
+//! each plugin's state to `<config_dir>/<plugin name>.json`.
+
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+  sync::{
+    broadcast::{self, Receiver},
+    Mutex,
+  },
+  task::JoinHandle,
+};
+use tracing::{error, warn};
+use ts_rs::TS;
+
+use super::{ConfigField, DanmujiPlugin, PluginContext};
+use crate::{
+  client::BiliMessage,
+  error::DanmujiError,
+  util::{load_json, save_json},
+  DanmujiResult,
+};
+
+/// Plugin as listed by the web API
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/PluginInfo.ts")]
+pub struct PluginInfo {
+  name: String,
+  description: String,
+  enabled: bool,
+  #[ts(type = "any")]
+  config: Value,
+  schema: Vec<ConfigField>,
+}
+
+/// Persisted state of a plugin
+#[derive(Debug, Serialize, Deserialize)]
+struct PluginState {
+  enabled: bool,
+  config: Value,
+}
+
+#[derive(Debug)]
+struct PluginEntry {
+  plugin: Box<dyn DanmujiPlugin>,
+  enabled: bool,
+}
+
+impl PluginEntry {
+  fn info(&self) -> PluginInfo {
+    PluginInfo {
+      name: self.plugin.name().to_string(),
+      description: self.plugin.description().to_string(),
+      enabled: self.enabled,
+      config: self.plugin.config(),
+      schema: self.plugin.config_schema(),
+    }
+  }
+
+  fn state(&self) -> PluginState {
+    PluginState {
+      enabled: self.enabled,
+      config: self.plugin.config(),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct PluginRegistry {
+  plugins: Arc<Mutex<Vec<PluginEntry>>>,
+  ctx: PluginContext,
+  // where plugin states are persisted
+  config_dir: PathBuf,
+  dispatcher: JoinHandle<()>,
+}
+
+impl PluginRegistry {
+  /// Take ownership of the plugins, restore their persisted states and start
+  /// feeding them messages from upstream. Plugins without a persisted state
+  /// are enabled with their default config.
+  pub fn start(
+    plugins: Vec<Box<dyn DanmujiPlugin>>,
+    upstream: Receiver<BiliMessage>,
+    ctx: PluginContext,
+    config_dir: impl Into<PathBuf>,
+  ) -> Self {
+    let config_dir = config_dir.into();
+    let plugins = plugins
+      .into_iter()
+      .map(|plugin| restore(plugin, &config_dir, &ctx))
+      .collect();
+    let plugins = Arc::new(Mutex::new(plugins));
+    let dispatcher = tokio::spawn(dispatch(upstream, plugins.clone(), ctx.clone()));
+
+    Self {
+      plugins,
+      ctx,
+      config_dir,
+      dispatcher,
+    }
+  }
+
+  /// all registered plugins
+  pub async fn list(&self) -> Vec<PluginInfo> {
+    let plugins = self.plugins.lock().await;
+    plugins.iter().map(PluginEntry::info).collect()
+  }
+
+  /// current config of a plugin
+  pub async fn config(&self, name: &str) -> DanmujiResult<Value> {
+    let mut plugins = self.plugins.lock().await;
+    let entry = find(&mut plugins, name)?;
+    Ok(entry.plugin.config())
+  }
+
+  /// start a plugin, no-op if it is already enabled
+  pub async fn enable(&self, name: &str) -> DanmujiResult<()> {
+    let mut plugins = self.plugins.lock().await;
+    let entry = find(&mut plugins, name)?;
+    if !entry.enabled {
+      entry.plugin.start(&self.ctx)?;
+      entry.enabled = true;
+    }
+    self.persist(entry);
+    Ok(())
+  }
+
+  /// stop a plugin, no-op if it is already disabled
+  pub async fn disable(&self, name: &str) -> DanmujiResult<()> {
+    let mut plugins = self.plugins.lock().await;
+    let entry = find(&mut plugins, name)?;
+    if entry.enabled {
+      entry.plugin.stop();
+      entry.enabled = false;
+    }
+    self.persist(entry);
+    Ok(())
+  }
+
+  /// change the config of a plugin, which keeps its current config
+  /// if the new one is rejected
+  pub async fn configure(&self, name: &str, config: Value) -> DanmujiResult<()> {
+    let mut plugins = self.plugins.lock().await;
+    let entry = find(&mut plugins, name)?;
+    entry.plugin.on_config_change(config)?;
+    self.persist(entry);
+    Ok(())
+  }
+
+  // the change has taken effect, so failing to save it is only logged
+  fn persist(&self, entry: &PluginEntry) {
+    let name = entry.plugin.name();
+    let saved = std::fs::create_dir_all(&self.config_dir)
+      .map_err(DanmujiError::from)
+      .and_then(|_| save_json(&entry.state(), state_path(&self.config_dir, name)));
+    if let Err(err) = saved {
+      warn!("Fail Saving Config of Plugin {}: {}", name, err);
+    }
+  }
+}
+
+impl Drop for PluginRegistry {
+  fn drop(&mut self) {
+    self.dispatcher.abort();
+    if let Ok(mut plugins) = self.plugins.try_lock() {
+      for entry in plugins.iter_mut().filter(|entry| entry.enabled) {
+        entry.plugin.stop();
+      }
+    }
+  }
+}
+
+fn state_path(config_dir: &Path, name: &str) -> PathBuf {
+  config_dir.join(format!("{}.json", name))
+}
+
+fn find<'a>(plugins: &'a mut [PluginEntry], name: &str) -> DanmujiResult<&'a mut PluginEntry> {
+  plugins
+    .iter_mut()
+    .find(|entry| entry.plugin.name() == name)
+    .ok_or_else(|| DanmujiError::PluginNotFound(name.to_string()))
+}
+
+// apply the persisted state of the plugin, if any, and start it if enabled
+fn restore(
+  mut plugin: Box<dyn DanmujiPlugin>,
+  config_dir: &Path,
+  ctx: &PluginContext,
+) -> PluginEntry {
+  let name = plugin.name();
+  let mut enabled = true;
+  if let Some(state) = load_json::<PluginState>(state_path(config_dir, name)) {
+    if let Err(err) = plugin.on_config_change(state.config) {
+      warn!("Ignoring Saved Config of Plugin {}: {}", name, err);
+    }
+    enabled = state.enabled;
+  }
+  if enabled {
+    if let Err(err) = plugin.start(ctx) {
+      error!("Fail Starting Plugin {}: {}", name, err);
+      enabled = false;
+    }
+  }
+  PluginEntry { plugin, enabled }
+}
+
+async fn dispatch(
+  mut upstream: Receiver<BiliMessage>,
+  plugins: Arc<Mutex<Vec<PluginEntry>>>,
+  ctx: PluginContext,
+) {
+  loop {
+    let msg = match upstream.recv().await {
+      Ok(msg) => msg,
+      Err(broadcast::error::RecvError::Lagged(skipped)) => {
+        warn!("Plugins Lagged, {} Messages Skipped", skipped);
+        continue;
+      }
+      Err(broadcast::error::RecvError::Closed) => {
+        error!("BiliClient dropped");
+        break;
+      }
+    };
+    let mut plugins = plugins.lock().await;
+    for entry in plugins.iter_mut().filter(|entry| entry.enabled) {
+      entry.plugin.on_message(&msg, &ctx);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use serde_json::json;
+  use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+  use super::*;
+  use crate::{
+    client::DanmuMessage,
+    plugins::{parse_config, ConfigFieldKind},
+    util::temp_dir,
+  };
+
+  #[derive(Debug, Clone, Serialize, Deserialize)]
+  struct EchoConfig {
+    prefix: String,
+  }
+
+  // replies every danmu with the configured prefix
+  #[derive(Debug)]
+  struct Echo {
+    config: EchoConfig,
+  }
+
+  impl DanmujiPlugin for Echo {
+    fn name(&self) -> &'static str {
+      "echo"
+    }
+
+    fn description(&self) -> &'static str {
+      "echo danmu"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+      vec![ConfigField::new("prefix", ConfigFieldKind::String, "")]
+    }
+
+    fn config(&self) -> Value {
+      serde_json::to_value(&self.config).unwrap()
+    }
+
+    fn start(&mut self, _ctx: &PluginContext) -> DanmujiResult<()> {
+      Ok(())
+    }
+
+    fn on_message(&mut self, msg: &BiliMessage, ctx: &PluginContext) {
+      if let BiliMessage::Danmu(danmu) = msg {
+        ctx.send_danmu(format!("{}{}", self.config.prefix, danmu.content()));
+      }
+    }
+
+    fn on_config_change(&mut self, config: Value) -> DanmujiResult<()> {
+      self.config = parse_config(config)?;
+      Ok(())
+    }
+
+    fn stop(&mut self) {}
+  }
+
+  fn echo() -> Box<dyn DanmujiPlugin> {
+    Box::new(Echo {
+      config: EchoConfig {
+        prefix: "echo: ".to_string(),
+      },
+    })
+  }
+
+  fn start_registry(
+    dir: &Path,
+  ) -> (
+    PluginRegistry,
+    broadcast::Sender<BiliMessage>,
+    UnboundedReceiver<String>,
+  ) {
+    let (tx, rx) = broadcast::channel(16);
+    let (sender_tx, sender_rx) = unbounded_channel();
+    let registry = PluginRegistry::start(vec![echo()], rx, PluginContext::new(sender_tx), dir);
+    (registry, tx, sender_rx)
+  }
+
+  async fn next_reply(rx: &mut UnboundedReceiver<String>) -> String {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+      .await
+      .unwrap()
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_dispatch_and_disable() {
+    let dir = temp_dir("plugin-dispatch");
+    let (registry, tx, mut rx) = start_registry(&dir);
+    let danmu = BiliMessage::Danmu(DanmuMessage::default_message());
+
+    let plugins = registry.list().await;
+    assert_eq!(1, plugins.len());
+    assert!(plugins[0].enabled);
+
+    tx.send(danmu.clone()).unwrap();
+    assert_eq!("echo: 你好Bilibili", next_reply(&mut rx).await);
+
+    registry.disable("echo").await.unwrap();
+    tx.send(danmu.clone()).unwrap();
+    // let the dispatcher see the danmu before enabling again
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    registry.enable("echo").await.unwrap();
+    tx.send(danmu).unwrap();
+    // only the danmu sent while enabled is echoed
+    assert_eq!("echo: 你好Bilibili", next_reply(&mut rx).await);
+    assert!(tokio::time::timeout(Duration::from_millis(200), rx.recv())
+      .await
+      .is_err());
+
+    assert!(matches!(
+      registry.enable("missing").await,
+      Err(DanmujiError::PluginNotFound(_))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_configure_and_restore() {
+    let dir = temp_dir("plugin-restore");
+    let (registry, _tx, _rx) = start_registry(&dir);
+
+    assert!(matches!(
+      registry.configure("echo", json!({ "prefix": 1 })).await,
+      Err(DanmujiError::InvalidPluginConfig(_))
+    ));
+    // the rejected config isn't applied
+    assert_eq!(
+      json!({ "prefix": "echo: " }),
+      registry.config("echo").await.unwrap()
+    );
+
+    registry
+      .configure("echo", json!({ "prefix": "> " }))
+      .await
+      .unwrap();
+    registry.disable("echo").await.unwrap();
+    drop(registry);
+
+    // a new registry picks up the persisted state
+    let (registry, _tx, _rx) = start_registry(&dir);
+    let plugins = registry.list().await;
+    assert!(!plugins[0].enabled);
+    assert_eq!(json!({ "prefix": "> " }), plugins[0].config);
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
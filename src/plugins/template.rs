@@ -0,0 +1,333 @@
+//! Thank templates, e.g., "感谢{uname}投喂的{gift_num}个{gift_name}~".
+//! Templates are compiled when configured and checked against sample
+//! events, so that broken templates are found before a real event arrives.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use self_cell::self_cell;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tinytemplate::TinyTemplate;
+use ts_rs::TS;
+
+use super::{
+  gift_thanker::GiftSummary,
+  guard_sc_thanker::{GuardThanks, SuperChatThanks},
+};
+use crate::client::{DanmuMessage, GiftMessage, GuardPurchaseMessage, SuperChatMessage};
+
+// every parsed template registers its text under this name
+const NAME: &str = "template";
+
+// at most this many parsed templates are kept by a thread
+const MAX_PARSED: usize = 64;
+
+type Parsed<'a> = TinyTemplate<'a>;
+
+self_cell!(
+  /// A TinyTemplate owning the text it is parsed from
+  struct ParsedTemplate {
+    owner: String,
+    #[covariant]
+    dependent: Parsed,
+  }
+);
+
+impl ParsedTemplate {
+  fn parse(source: &str) -> Result<Self, TemplateError> {
+    #[cfg(test)]
+    PARSE_COUNT.with(|count| count.set(count.get() + 1));
+    ParsedTemplate::try_new(source.to_string(), |text| {
+      let mut template = TinyTemplate::new();
+      template
+        .add_template(NAME, text)
+        .map_err(|err| TemplateError::new(err, text))?;
+      Ok(template)
+    })
+  }
+}
+
+thread_local! {
+  // TinyTemplate isn't Send because of its boxed formatters, while plugins
+  // and the tasks they spawn must be, so a parsed template can't be kept by
+  // its plugin. Instead every thread rendering a template parses it once and
+  // keeps it here, keyed by the template text
+  static PARSED: RefCell<HashMap<String, ParsedTemplate>> = RefCell::new(HashMap::new());
+}
+
+#[cfg(test)]
+thread_local! {
+  // how many times the thread has parsed a template
+  static PARSE_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// A template checked to compile, which can be kept by plugins.
+/// It is Send, holding only the text, and is parsed on the first render
+/// in each thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledTemplate {
+  source: String,
+}
+
+impl CompiledTemplate {
+  pub fn compile(source: &str) -> Result<Self, TemplateError> {
+    ParsedTemplate::parse(source)?;
+    Ok(Self {
+      source: source.to_string(),
+    })
+  }
+
+  pub fn render(&self, context: &impl Serialize) -> Result<String, TemplateError> {
+    PARSED.with(|parsed| {
+      let mut parsed = parsed.borrow_mut();
+      if !parsed.contains_key(&self.source) {
+        // templates of old configs are dropped once in a while
+        if parsed.len() >= MAX_PARSED {
+          parsed.clear();
+        }
+        parsed.insert(self.source.clone(), ParsedTemplate::parse(&self.source)?);
+      }
+      parsed[&self.source]
+        .borrow_dependent()
+        .render(NAME, context)
+        .map_err(|err| TemplateError::new(err, &self.source))
+    })
+  }
+}
+
+/// Why a template fails to compile or render
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/TemplateError.ts")]
+pub struct TemplateError {
+  msg: String,
+  // where the error is in the template, starting from 1, if known.
+  // The column counts characters, not bytes
+  line: Option<u32>,
+  column: Option<u32>,
+}
+
+impl TemplateError {
+  fn new(err: tinytemplate::error::Error, source: &str) -> Self {
+    use tinytemplate::error::Error;
+    let (msg, position) = match err {
+      Error::ParseError { msg, line, column } | Error::RenderError { msg, line, column } => {
+        (msg, Some((line, column)))
+      }
+      Error::CalledTemplateError {
+        ref err,
+        line,
+        column,
+        ..
+      }
+      | Error::CalledFormatterError {
+        ref err,
+        line,
+        column,
+        ..
+      } => (err.to_string(), Some((line, column))),
+      err => (err.to_string(), None),
+    };
+    // TinyTemplate counts the column in bytes
+    let position = position.map(|(line, column)| {
+      let chars = source
+        .lines()
+        .nth(line.saturating_sub(1))
+        .and_then(|text| text.get(..column.saturating_sub(1)))
+        .map_or(column, |before| before.chars().count() + 1);
+      (line, chars)
+    });
+    TemplateError {
+      msg,
+      line: position.map(|(line, _)| line as u32),
+      column: position.map(|(_, column)| column as u32),
+    }
+  }
+}
+
+impl std::fmt::Display for TemplateError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match (self.line, self.column) {
+      (Some(line), Some(column)) => write!(f, "{} (line {}, column {})", self.msg, line, column),
+      _ => write!(f, "{}", self.msg),
+    }
+  }
+}
+
+/// The kinds of events templates are rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[ts(export_to = "frontend/src/bindings/TemplateEvent.ts")]
+pub enum TemplateEvent {
+  /// a gift thanked right away
+  Gift,
+  /// gifts of a user thanked together
+  GiftSummary,
+  /// a guard purchase or renewal
+  GuardPurchase,
+  /// a super chat
+  SuperChat,
+}
+
+impl TemplateEvent {
+  /// the event templates are previewed and checked against
+  pub fn sample(&self) -> Value {
+    let sample = match self {
+      TemplateEvent::Gift => serde_json::to_value(GiftMessage::default_message()),
+      TemplateEvent::GiftSummary => serde_json::to_value(GiftSummary::default_message()),
+      TemplateEvent::GuardPurchase => {
+        serde_json::to_value(GuardThanks::new(&GuardPurchaseMessage::default_message()))
+      }
+      TemplateEvent::SuperChat => serde_json::to_value(SuperChatThanks::new(
+        &SuperChatMessage::default_message(),
+        10,
+      )),
+    };
+    let mut sample = sample.unwrap_or_default();
+    fill_optional(*self, &mut sample);
+    sample
+  }
+
+  /// variables templates of the event can use, e.g., "uname" or "medal.level"
+  pub fn variables(&self) -> Vec<String> {
+    let mut variables = vec![];
+    collect_variables("", &self.sample(), &mut variables);
+    variables
+  }
+}
+
+// the default messages leave optional fields empty, which would hide them
+// from `variables` and fail previews of templates using them
+fn fill_optional(event: TemplateEvent, sample: &mut Value) {
+  match event {
+    TemplateEvent::Gift => {
+      let danmu = serde_json::to_value(DanmuMessage::default_message()).unwrap_or_default();
+      sample["medal"] = danmu["medal"].clone();
+      sample["tid"] = json!("0");
+      sample["batch_combo_id"] = json!("batch:gift:combo_id:0");
+      sample["blind_gift"] = json!({
+        "original_gift_id": 0,
+        "original_gift_name": "心动盲盒",
+        "original_gift_price": 15000,
+      });
+      sample["receiver"] = json!({ "uid": 0, "uname": "测试主播" });
+    }
+    TemplateEvent::SuperChat => {
+      sample["message_trans"] = json!("配信者が今日歌った歌はとても良かった、明日も来ます!");
+    }
+    TemplateEvent::GiftSummary | TemplateEvent::GuardPurchase => {}
+  }
+}
+
+// paths of the values that can be printed, objects are not printable
+fn collect_variables(prefix: &str, value: &Value, variables: &mut Vec<String>) {
+  match value {
+    Value::Object(fields) => {
+      for (name, value) in fields {
+        let path = if prefix.is_empty() {
+          name.clone()
+        } else {
+          format!("{}.{}", prefix, name)
+        };
+        collect_variables(&path, value, variables);
+      }
+    }
+    _ => variables.push(prefix.to_string()),
+  }
+}
+
+/// compile the template and render it with the sample event, which catches
+/// variables the event doesn't have
+pub fn preview(template: &str, event: TemplateEvent) -> Result<String, TemplateError> {
+  CompiledTemplate::compile(template)?.render(&event.sample())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compile_error_position() {
+    let err = CompiledTemplate::compile("感谢{uname}\n投喂的{gift_num").unwrap_err();
+    assert_eq!(Some(2), err.line);
+    assert!(err.column.is_some());
+    assert!(err.to_string().contains("line 2"));
+  }
+
+  #[test]
+  fn test_preview() {
+    assert_eq!(
+      Ok("感谢测试用户投喂的1个小花花~".to_string()),
+      preview(
+        "感谢{uname}投喂的{gift_num}个{gift_name}~",
+        TemplateEvent::Gift
+      )
+    );
+    assert_eq!(
+      Ok("感谢测试用户投喂的1个小花花、5个辣条~".to_string()),
+      preview("感谢{uname}投喂的{gifts}~", TemplateEvent::GiftSummary)
+    );
+
+    assert_eq!(
+      Ok("感谢40级哈哈哈的测试用户~".to_string()),
+      preview(
+        "感谢{medal.level}级{medal.name}的{uname}~",
+        TemplateEvent::Gift
+      )
+    );
+
+    // gifts are only known to summaries
+    let err = preview("感谢{uname}投喂的{gifts}~", TemplateEvent::Gift).unwrap_err();
+    assert_eq!((Some(1), Some(13)), (err.line, err.column));
+  }
+
+  #[test]
+  fn test_variables() {
+    let variables = TemplateEvent::Gift.variables();
+    for variable in ["uname", "gift_name", "gift_num", "total_coin"] {
+      assert!(variables.contains(&variable.to_string()), "{}", variable);
+    }
+    for variable in ["medal.level", "medal.name", "receiver.uname", "tid"] {
+      assert!(variables.contains(&variable.to_string()), "{}", variable);
+    }
+    // optional fields filled in the sample are still valid gifts
+    serde_json::from_value::<GiftMessage>(TemplateEvent::Gift.sample()).unwrap();
+    let variables = TemplateEvent::GiftSummary.variables();
+    assert!(variables.contains(&"gifts".to_string()));
+    assert!(!variables.contains(&"gift_name".to_string()));
+    let variables = TemplateEvent::GuardPurchase.variables();
+    assert!(variables.contains(&"guard_name".to_string()));
+    assert!(variables.contains(&"months".to_string()));
+    let variables = TemplateEvent::SuperChat.variables();
+    assert!(variables.contains(&"excerpt".to_string()));
+    assert!(variables.contains(&"message".to_string()));
+  }
+
+  #[test]
+  fn test_compiled_template_is_reusable() {
+    let template = CompiledTemplate::compile("{uname}").unwrap();
+    let cloned = template.clone();
+    drop(template);
+    let context = serde_json::json!({ "uname": "甲" });
+    assert_eq!("甲", cloned.render(&context).unwrap());
+    assert_eq!("甲", cloned.render(&context).unwrap());
+    // other threads parse their own copy
+    let rendered = std::thread::spawn(move || cloned.render(&context).unwrap());
+    assert_eq!("甲", rendered.join().unwrap());
+  }
+
+  #[test]
+  fn test_render_parses_once() {
+    let parses = || PARSE_COUNT.with(|count| count.get());
+    let template = CompiledTemplate::compile("{uname}").unwrap();
+    let context = serde_json::json!({ "uname": "甲" });
+    let before = parses();
+    template.render(&context).unwrap();
+    assert_eq!(before + 1, parses());
+    // renders after the first one hit the cache
+    for _ in 0..3 {
+      assert_eq!("甲", template.render(&context).unwrap());
+    }
+    assert_eq!(before + 1, parses());
+  }
+}
@@ -12,11 +12,13 @@ lazy_static! {
     pub static ref USER_CONFIG: PathBuf = PROJECT_ROOT.join("user-config.json");
     /// Room Config File Path
     pub static ref ROOM_CONFIG: PathBuf = PROJECT_ROOT.join("room-config.json");
-    /// Gift Thank Config File Path
+    /// Gift Thank Config File Path, superseded by the gift thanker's plugin config
     pub static ref THANK_CONFIG: PathBuf = PROJECT_ROOT.join("thank-config.json");
+    /// Directory of Plugin Config Files
+    pub static ref PLUGIN_CONFIG_DIR: PathBuf = PROJECT_ROOT.join("plugin-config");
 }
 
-fn save_json(object: &impl Serialize, path: impl AsRef<Path>) -> DanmujiResult<()> {
+pub fn save_json(object: &impl Serialize, path: impl AsRef<Path>) -> DanmujiResult<()> {
   let file = OpenOptions::new()
     .write(true)
     .create(true)
@@ -28,7 +30,7 @@ fn save_json(object: &impl Serialize, path: impl AsRef<Path>) -> DanmujiResult<(
   Ok(())
 }
 
-fn load_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
+pub fn load_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
   let file = OpenOptions::new().read(true).open(path).ok()?;
   let reader = BufReader::new(file);
   serde_json::from_reader(reader).ok()
@@ -42,10 +44,6 @@ pub fn save_room_config(config: &RoomConfig) -> DanmujiResult<()> {
   save_json(config, ROOM_CONFIG.as_path())
 }
 
-pub fn save_thank_config(config: &GiftThankConfig) -> DanmujiResult<()> {
-  save_json(config, THANK_CONFIG.as_path())
-}
-
 pub fn load_user_config() -> Option<UserConfig> {
   load_json(USER_CONFIG.as_path())
 }
@@ -67,3 +65,12 @@ pub fn delete_room_config() -> DanmujiResult<()> {
   std::fs::remove_file(ROOM_CONFIG.as_path())?;
   Ok(())
 }
+
+/// a fresh directory for a test's files, which the test removes when done
+#[cfg(test)]
+pub fn temp_dir(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("danmuji-{}-{name}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  std::fs::create_dir_all(&dir).unwrap();
+  dir
+}